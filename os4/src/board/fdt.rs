@@ -0,0 +1,187 @@
+//! A minimal reader for the flattened device tree (FDT) blob
+//!
+//! Only what the kernel needs at boot is implemented: walking the structure
+//! block node by node and looking up properties of a node. Nothing here
+//! allocates, so it can be used before the heap is ready.
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+/// maximum nesting of nodes we keep `#address-cells`/`#size-cells` for
+const MAX_DEPTH: usize = 16;
+
+/// `#address-cells` and `#size-cells` defaults from the devicetree spec
+const DEFAULT_CELLS: (usize, usize) = (2, 1);
+
+fn align4(v: usize) -> usize {
+    (v + 3) & !3
+}
+
+/// a device tree blob in memory
+pub struct Fdt<'a> {
+    data: &'a [u8],
+    struct_off: usize,
+    strings_off: usize,
+}
+
+impl<'a> Fdt<'a> {
+    /// Check the header at `addr` and wrap the whole blob.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must be readable for the size stated in the header.
+    pub unsafe fn from_addr(addr: usize) -> Option<Self> {
+        if addr == 0 || addr % 4 != 0 {
+            return None;
+        }
+        let header = core::slice::from_raw_parts(addr as *const u8, 40);
+        if read_be32(header, 0)? != FDT_MAGIC {
+            return None;
+        }
+        let total_size = read_be32(header, 4)? as usize;
+        let data = core::slice::from_raw_parts(addr as *const u8, total_size);
+        Some(Self {
+            data,
+            struct_off: read_be32(header, 8)? as usize,
+            strings_off: read_be32(header, 12)? as usize,
+        })
+    }
+    fn be32(&self, off: usize) -> Option<u32> {
+        read_be32(self.data, off)
+    }
+    /// NUL-terminated string starting at `off`
+    fn cstr(&self, off: usize) -> Option<&'a str> {
+        let rest = self.data.get(off..)?;
+        let len = rest.iter().position(|b| *b == 0)?;
+        core::str::from_utf8(&rest[..len]).ok()
+    }
+    /// Call `f` on every node in depth-first order.
+    pub fn for_each_node(&self, mut f: impl FnMut(&FdtNode<'a, '_>)) {
+        // cells[d] is what a node at depth d uses to decode its `reg`
+        let mut cells = [DEFAULT_CELLS; MAX_DEPTH + 1];
+        let mut depth = 0;
+        let mut off = self.struct_off;
+        while let Some(token) = self.be32(off) {
+            off += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = match self.cstr(off) {
+                        Some(name) => name,
+                        None => return,
+                    };
+                    off = align4(off + name.len() + 1);
+                    let node = FdtNode {
+                        fdt: self,
+                        name,
+                        props_off: off,
+                        address_cells: cells[depth.min(MAX_DEPTH)].0,
+                        size_cells: cells[depth.min(MAX_DEPTH)].1,
+                    };
+                    if depth < MAX_DEPTH {
+                        cells[depth + 1] = (
+                            node.property_u32("#address-cells")
+                                .map_or(DEFAULT_CELLS.0, |v| v as usize),
+                            node.property_u32("#size-cells")
+                                .map_or(DEFAULT_CELLS.1, |v| v as usize),
+                        );
+                    }
+                    f(&node);
+                    depth += 1;
+                }
+                FDT_END_NODE => {
+                    if depth == 0 {
+                        return;
+                    }
+                    depth -= 1;
+                }
+                FDT_PROP => match self.be32(off) {
+                    Some(len) => off = align4(off + 8 + len as usize),
+                    None => return,
+                },
+                FDT_NOP => {}
+                // FDT_END or a corrupted blob
+                _ => return,
+            }
+        }
+    }
+}
+
+/// one node of the tree, handed out by [`Fdt::for_each_node`]
+pub struct FdtNode<'a, 'f> {
+    fdt: &'f Fdt<'a>,
+    /// unit name, e.g. `memory@80000000`
+    pub name: &'a str,
+    props_off: usize,
+    address_cells: usize,
+    size_cells: usize,
+}
+
+impl<'a, 'f> FdtNode<'a, 'f> {
+    /// node name without the unit address
+    pub fn base_name(&self) -> &'a str {
+        self.name.split('@').next().unwrap_or(self.name)
+    }
+    /// Raw value of property `name`. Properties always precede subnodes.
+    pub fn property(&self, name: &str) -> Option<&'a [u8]> {
+        let fdt = self.fdt;
+        let mut off = self.props_off;
+        loop {
+            match fdt.be32(off)? {
+                FDT_PROP => {
+                    let len = fdt.be32(off + 4)? as usize;
+                    let name_off = fdt.be32(off + 8)? as usize;
+                    let value = fdt.data.get(off + 12..off + 12 + len)?;
+                    if fdt.cstr(fdt.strings_off + name_off)? == name {
+                        return Some(value);
+                    }
+                    off = align4(off + 12 + len);
+                }
+                FDT_NOP => off += 4,
+                _ => return None,
+            }
+        }
+    }
+    /// a property holding a single `u32`
+    pub fn property_u32(&self, name: &str) -> Option<u32> {
+        read_be32(self.property(name)?, 0)
+    }
+    /// a property holding a single `u32` or `u64`, widened
+    pub fn property_usize(&self, name: &str) -> Option<usize> {
+        let value = self.property(name)?;
+        read_cells(value, 0, value.len() / 4)
+    }
+    /// Whether `compatible` lists `compat`.
+    pub fn is_compatible(&self, compat: &str) -> bool {
+        self.property("compatible").map_or(false, |list| {
+            list.split(|b| *b == 0).any(|s| s == compat.as_bytes())
+        })
+    }
+    /// The `index`-th `(base, size)` pair of the `reg` property.
+    pub fn reg(&self, index: usize) -> Option<(usize, usize)> {
+        let reg = self.property("reg")?;
+        let entry = (self.address_cells + self.size_cells) * 4;
+        let off = index * entry;
+        if entry == 0 || off + entry > reg.len() {
+            return None;
+        }
+        let base = read_cells(reg, off, self.address_cells)?;
+        let size = read_cells(reg, off + self.address_cells * 4, self.size_cells)?;
+        Some((base, size))
+    }
+}
+
+fn read_be32(data: &[u8], off: usize) -> Option<u32> {
+    let bytes = data.get(off..off + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// combine `n` big-endian cells starting at `off` into one number
+fn read_cells(data: &[u8], off: usize, n: usize) -> Option<usize> {
+    (0..n).try_fold(0usize, |acc, i| {
+        Some((acc << 32) | read_be32(data, off + i * 4)? as usize)
+    })
+}
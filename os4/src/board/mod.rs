@@ -0,0 +1,221 @@
+//! Description of the machine we are running on
+//!
+//! The SBI hands us the physical address of a flattened device tree in `a1`.
+//! [`init()`] parses it once at boot and records what the rest of the kernel
+//! needs: where physical memory ends, how fast `mtime` ticks and which MMIO
+//! regions must be mapped into kernel space. If there is no usable device
+//! tree we fall back to the layout of QEMU's default `virt` machine.
+//!
+//! The kernel command line in `/chosen/bootargs` is kept as well, see
+//! [`boot_param()`], and so are the ids of the harts in `/cpus`.
+
+mod fdt;
+
+use crate::config::{DEFAULT_CLOCK_FREQ, DEFAULT_MEMORY_END};
+use crate::sync::SpinLock;
+use alloc::string::String;
+use fdt::Fdt;
+use lazy_static::*;
+
+/// at most this many MMIO regions are recorded
+pub const MAX_MMIO_REGIONS: usize = 16;
+/// longer kernel command lines are truncated
+pub const MAX_BOOTARGS_LEN: usize = 256;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+/// kinds of devices we know how to look for
+pub enum MmioKind {
+    Uart,
+    Virtio,
+    /// QEMU's sifive_test, used to power off with an exit code
+    Test,
+}
+
+#[derive(Copy, Clone, Debug)]
+/// a physical MMIO window of a device
+pub struct MmioRegion {
+    pub kind: MmioKind,
+    pub base: usize,
+    pub size: usize,
+}
+
+#[derive(Copy, Clone)]
+/// everything the kernel learned from the device tree
+pub struct BoardInfo {
+    /// start of physical memory
+    pub memory_start: usize,
+    /// end (exclusive) of physical memory
+    pub memory_end: usize,
+    /// frequency of the `time` CSR in Hz
+    pub clock_freq: usize,
+    /// bit `i` is set if hart `i` is there and enabled
+    pub hart_mask: usize,
+    mmio: [Option<MmioRegion>; MAX_MMIO_REGIONS],
+    bootargs: [u8; MAX_BOOTARGS_LEN],
+    bootargs_len: usize,
+}
+
+impl Default for BoardInfo {
+    fn default() -> Self {
+        Self {
+            memory_start: 0x8000_0000,
+            memory_end: DEFAULT_MEMORY_END,
+            clock_freq: DEFAULT_CLOCK_FREQ,
+            hart_mask: 0,
+            mmio: [None; MAX_MMIO_REGIONS],
+            bootargs: [0; MAX_BOOTARGS_LEN],
+            bootargs_len: 0,
+        }
+    }
+}
+
+impl BoardInfo {
+    /// iterate over the discovered MMIO regions
+    pub fn mmio_regions(&self) -> impl Iterator<Item = MmioRegion> + '_ {
+        self.mmio.iter().flatten().copied()
+    }
+    /// the kernel command line, empty if there was none
+    pub fn bootargs(&self) -> &str {
+        core::str::from_utf8(&self.bootargs[..self.bootargs_len]).unwrap_or("")
+    }
+    fn set_bootargs(&mut self, value: &[u8]) {
+        let value = value.split(|b| *b == 0).next().unwrap_or(&[]);
+        let len = value.len().min(MAX_BOOTARGS_LEN);
+        if len < value.len() {
+            warn!("[board] bootargs longer than {} bytes truncated", len);
+        }
+        self.bootargs[..len].copy_from_slice(&value[..len]);
+        self.bootargs_len = len;
+    }
+    fn add_mmio(&mut self, kind: MmioKind, base: usize, size: usize) {
+        match self.mmio.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => *slot = Some(MmioRegion { kind, base, size }),
+            None => warn!(
+                "[board] too many MMIO regions, {:?} at {:#x} ignored",
+                kind, base
+            ),
+        }
+    }
+    /// Fill in the fields from the device tree at `dtb_pa`.
+    fn parse(&mut self, dtb_pa: usize) -> bool {
+        let fdt = match unsafe { Fdt::from_addr(dtb_pa) } {
+            Some(fdt) => fdt,
+            None => return false,
+        };
+        let mut memory_found = false;
+        fdt.for_each_node(|node| {
+            let is_memory = node.base_name() == "memory"
+                || node
+                    .property("device_type")
+                    .map_or(false, |t| t.starts_with(b"memory\0"));
+            if is_memory && !memory_found {
+                if let Some((base, size)) = node.reg(0) {
+                    self.memory_start = base;
+                    self.memory_end = base + size;
+                    memory_found = true;
+                }
+            }
+            if node.name == "chosen" {
+                if let Some(bootargs) = node.property("bootargs") {
+                    self.set_bootargs(bootargs);
+                }
+            }
+            // usually in `/cpus`, but some trees put it in each `cpu@N`
+            if let Some(freq) = node.property_usize("timebase-frequency") {
+                self.clock_freq = freq;
+            }
+            let is_cpu = node
+                .property("device_type")
+                .map_or(false, |t| t.starts_with(b"cpu\0"));
+            let enabled = node
+                .property("status")
+                .map_or(true, |s| s.starts_with(b"okay\0"));
+            if is_cpu && enabled {
+                match node.reg(0) {
+                    Some((hartid, _)) if hartid < usize::BITS as usize => {
+                        self.hart_mask |= 1 << hartid
+                    }
+                    _ => warn!("[board] cpu node {} without a usable hart id", node.name),
+                }
+            }
+            let kind = if node.is_compatible("ns16550a") {
+                Some(MmioKind::Uart)
+            } else if node.is_compatible("virtio,mmio") {
+                Some(MmioKind::Virtio)
+            } else if node.is_compatible("sifive,test0") {
+                Some(MmioKind::Test)
+            } else {
+                None
+            };
+            if let (Some(kind), Some((base, size))) = (kind, node.reg(0)) {
+                self.add_mmio(kind, base, size);
+            }
+        });
+        true
+    }
+}
+
+lazy_static! {
+    /// board description, filled in by [`init()`]
+    static ref BOARD_INFO: SpinLock<BoardInfo> =
+        SpinLock::new(BoardInfo::default());
+}
+
+/// Parse the device tree passed by the SBI. Must run before `mm::init()`,
+/// as the blob itself lives in memory the frame allocator will hand out.
+pub fn init(dtb_pa: usize) {
+    let mut info = BOARD_INFO.lock();
+    if !info.parse(dtb_pa) {
+        warn!("[board] no device tree at {:#x}, using defaults", dtb_pa);
+    }
+    info!(
+        "[board] memory [{:#x}, {:#x}), timebase {} Hz, harts {:#b}",
+        info.memory_start, info.memory_end, info.clock_freq, info.hart_mask
+    );
+    if !info.bootargs().is_empty() {
+        info!("[board] bootargs \"{}\"", info.bootargs());
+    }
+    for region in info.mmio_regions() {
+        info!(
+            "[board] {:?} at [{:#x}, {:#x})",
+            region.kind,
+            region.base,
+            region.base + region.size
+        );
+    }
+}
+
+/// a copy of the board description
+pub fn board_info() -> BoardInfo {
+    *BOARD_INFO.lock()
+}
+
+/// end (exclusive) of physical memory
+pub fn memory_end() -> usize {
+    BOARD_INFO.lock().memory_end
+}
+
+/// frequency of the `time` CSR in Hz
+pub fn clock_freq() -> usize {
+    BOARD_INFO.lock().clock_freq
+}
+
+/// the harts found in the device tree, bit `i` for hart `i`. 0 if there was
+/// no device tree: then we only know of the one we boot on
+pub fn hart_mask() -> usize {
+    BOARD_INFO.lock().hart_mask
+}
+
+/// Value of `key=value` on the kernel command line, `Some("")` for a bare
+/// `key`.
+pub fn boot_param(key: &str) -> Option<String> {
+    let info = BOARD_INFO.lock();
+    info.bootargs().split_whitespace().find_map(|arg| {
+        let mut kv = arg.splitn(2, '=');
+        if kv.next() == Some(key) {
+            Some(String::from(kv.next().unwrap_or("")))
+        } else {
+            None
+        }
+    })
+}
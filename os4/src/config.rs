@@ -3,20 +3,50 @@
 pub const USER_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
+/// harts with a larger id are left alone
+pub const MAX_HARTS: usize = 8;
+/// boot stack of each hart, which its idle loop keeps using
+pub const BOOT_STACK_SIZE: usize = 4096 * 16;
 // a little conflict about the question 
 // why the Physical block interval is using different calculate ways which one floor, one ceil 
-pub const MEMORY_END: usize = 0x80800000;
+// only used when the SBI gives us no device tree, see `board`
+pub const DEFAULT_MEMORY_END: usize = 0x80800000;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
 pub const MAX_SYSCALL_NUM: usize = 500;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
-/// Return (bottom, top) of a kernel stack in kernel space.
-pub fn kernel_stack_position(app_id: usize) -> (usize, usize) {
-    let top = TRAMPOLINE - app_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
+/// end of the lower half of Sv39, the only part user mappings may use
+pub const USER_SPACE_END: usize = 1 << 38;
+/// user-executable page that calls `sigreturn`, where signal handlers
+/// return to. It is the last page of the user half, so mappings stay below
+pub const SIGRETURN_TRAMPOLINE: usize = USER_SPACE_END - PAGE_SIZE;
+/// where the search starts when the kernel picks an mmap address
+pub const MMAP_BASE: usize = 0x1000_0000;
+/// Return (bottom, top) of the kernel stack in `slot` in kernel space.
+pub fn kernel_stack_position(slot: usize) -> (usize, usize) {
+    let top = TRAMPOLINE - slot * (KERNEL_STACK_SIZE + PAGE_SIZE);
     let bottom = top - KERNEL_STACK_SIZE;
     (bottom, top)
 }
 
-pub const CLOCK_FREQ: usize = 12500000;
+pub const DEFAULT_CLOCK_FREQ: usize = 12500000;
+
+/// messages a mailbox holds at most
+pub const MAX_MAIL_NUM: usize = 16;
+/// bytes of a message at most, longer ones are cut
+pub const MAX_MAIL_LEN: usize = 256;
+
+/// CPU time in milliseconds a task may use until it calls
+/// `sys_set_cpu_limit`, 0 for no limit. The `cpulimit=` boot parameter
+/// overrides it
+pub const DEFAULT_CPU_LIMIT_MS: usize = 0;
+
+/// exits the shutdown summary lists at most, the latest ones
+pub const EXIT_LOG_LEN: usize = 64;
+
+/// priority of a task until it calls `sys_set_priority`
+pub const DEFAULT_PRIORITY: usize = 16;
+/// a task's stride is `BIG_STRIDE / priority`
+pub const BIG_STRIDE: u64 = 1 << 32;
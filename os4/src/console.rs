@@ -1,8 +1,12 @@
 //! SBI console driver, for text output
 
 use crate::sbi::console_putchar;
+use crate::sync::SpinLock;
 use core::fmt::{self, Write};
 
+/// held while printing, so that lines from different harts do not mix
+static CONSOLE_LOCK: SpinLock<()> = SpinLock::new(());
+
 struct Stdout;
 
 impl Write for Stdout {
@@ -15,9 +19,18 @@ impl Write for Stdout {
 }
 
 pub fn print(args: fmt::Arguments) {
+    let _guard = CONSOLE_LOCK.lock();
     Stdout.write_fmt(args).unwrap();
 }
 
+/// Write the raw bytes of `pieces`, which need not be UTF-8, in one go.
+pub fn write_bytes<'a>(pieces: impl IntoIterator<Item = &'a [u8]>) {
+    let _guard = CONSOLE_LOCK.lock();
+    for byte in pieces.into_iter().flatten() {
+        console_putchar(*byte as usize);
+    }
+}
+
 #[macro_export]
 /// print string macro
 macro_rules! print {
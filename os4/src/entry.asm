@@ -1,12 +1,57 @@
     .section .text.entry
     .globl _start
 _start:
+    # a0 = hartid, which we keep in tp
+    li t0, {max_harts}
+    bgeu a0, t0, .Lboot_hart_too_large
+    mv tp, a0
+    # every hart has a slice of boot_stack, which its idle loop keeps using
     la sp, boot_stack_top
+    li t0, {boot_stack_size}
+    mul t0, a0, t0
+    sub sp, sp, t0
     call rust_main
 
+    # where the other harts start, see start_other_harts in main.rs
+    .globl _start_secondary
+_start_secondary:
+    mv tp, a0
+    la sp, boot_stack_top
+    li t0, {boot_stack_size}
+    mul t0, a0, t0
+    sub sp, sp, t0
+    call rust_main_secondary
+
+    # there is no stack for us: tell through the legacy SBI console, then
+    # power off with a failure status
+.Lboot_hart_too_large:
+    la t1, .Ltoo_large_msg
+.Lputs:
+    lbu a0, 0(t1)
+    beqz a0, .Lshutdown
+    li a7, 1
+    ecall
+    addi t1, t1, 1
+    j .Lputs
+.Lshutdown:
+    li a7, 0x53525354
+    li a6, 0
+    li a0, 0
+    li a1, 1
+    ecall
+    li a7, 8
+    ecall
+.Lpark:
+    wfi
+    j .Lpark
+
+    .section .rodata
+.Ltoo_large_msg:
+    .asciz "[kernel] boot hart id is not below MAX_HARTS\n"
+
     .section .bss.stack
     .globl boot_stack
 boot_stack:
-    .space 4096 * 16
+    .space {boot_stack_size} * {max_harts}
     .globl boot_stack_top
-boot_stack_top:
\ No newline at end of file
+boot_stack_top:
@@ -0,0 +1,21 @@
+//! Files, what user programs reach through file descriptors
+//!
+//! There is the console, as stdin, stdout and stderr, and pipes.
+
+mod pipe;
+mod stdio;
+
+use crate::mm::UserBuffer;
+
+/// something a file descriptor can refer to
+pub trait File: Send + Sync {
+    fn readable(&self) -> bool;
+    fn writable(&self) -> bool;
+    /// Read into `buf`, returns the number of bytes read.
+    fn read(&self, buf: UserBuffer) -> usize;
+    /// Write out `buf`, returns the number of bytes written.
+    fn write(&self, buf: UserBuffer) -> usize;
+}
+
+pub use pipe::{make_pipe, Pipe};
+pub use stdio::{Stderr, Stdin, Stdout};
@@ -0,0 +1,171 @@
+//! Anonymous pipes: a ring buffer with a read end and a write end
+//!
+//! Both ends are [`File`]s. A read waits until it has filled its buffer or
+//! every write end is closed, a write waits until all of it went in or every
+//! read end is closed. Waiting yields to the scheduler.
+
+use super::File;
+use crate::mm::UserBuffer;
+use crate::sync::SpinLock;
+use crate::task::suspend_current_and_run_next;
+use alloc::sync::{Arc, Weak};
+
+/// one end of a pipe
+pub struct Pipe {
+    readable: bool,
+    writable: bool,
+    buffer: Arc<SpinLock<PipeRingBuffer>>,
+}
+
+impl Pipe {
+    fn read_end_with_buffer(buffer: Arc<SpinLock<PipeRingBuffer>>) -> Self {
+        Self {
+            readable: true,
+            writable: false,
+            buffer,
+        }
+    }
+    fn write_end_with_buffer(buffer: Arc<SpinLock<PipeRingBuffer>>) -> Self {
+        Self {
+            readable: false,
+            writable: true,
+            buffer,
+        }
+    }
+}
+
+const RING_BUFFER_SIZE: usize = 32;
+
+#[derive(Copy, Clone, PartialEq)]
+enum RingBufferStatus {
+    Full,
+    Empty,
+    Normal,
+}
+
+pub struct PipeRingBuffer {
+    arr: [u8; RING_BUFFER_SIZE],
+    head: usize,
+    tail: usize,
+    status: RingBufferStatus,
+    /// the ends, gone once every file descriptor for them is closed
+    read_end: Option<Weak<Pipe>>,
+    write_end: Option<Weak<Pipe>>,
+}
+
+impl PipeRingBuffer {
+    fn new() -> Self {
+        Self {
+            arr: [0; RING_BUFFER_SIZE],
+            head: 0,
+            tail: 0,
+            status: RingBufferStatus::Empty,
+            read_end: None,
+            write_end: None,
+        }
+    }
+    fn write_byte(&mut self, byte: u8) {
+        self.status = RingBufferStatus::Normal;
+        self.arr[self.tail] = byte;
+        self.tail = (self.tail + 1) % RING_BUFFER_SIZE;
+        if self.tail == self.head {
+            self.status = RingBufferStatus::Full;
+        }
+    }
+    fn read_byte(&mut self) -> u8 {
+        self.status = RingBufferStatus::Normal;
+        let byte = self.arr[self.head];
+        self.head = (self.head + 1) % RING_BUFFER_SIZE;
+        if self.head == self.tail {
+            self.status = RingBufferStatus::Empty;
+        }
+        byte
+    }
+    fn available_read(&self) -> usize {
+        if self.status == RingBufferStatus::Empty {
+            0
+        } else if self.tail > self.head {
+            self.tail - self.head
+        } else {
+            self.tail + RING_BUFFER_SIZE - self.head
+        }
+    }
+    fn available_write(&self) -> usize {
+        RING_BUFFER_SIZE - self.available_read()
+    }
+    fn all_read_ends_closed(&self) -> bool {
+        self.read_end.as_ref().unwrap().upgrade().is_none()
+    }
+    fn all_write_ends_closed(&self) -> bool {
+        self.write_end.as_ref().unwrap().upgrade().is_none()
+    }
+}
+
+/// Return (read_end, write_end) of a new pipe.
+pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
+    let buffer = Arc::new(SpinLock::new(PipeRingBuffer::new()));
+    let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
+    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
+    let mut ring = buffer.lock();
+    ring.read_end = Some(Arc::downgrade(&read_end));
+    ring.write_end = Some(Arc::downgrade(&write_end));
+    drop(ring);
+    (read_end, write_end)
+}
+
+impl File for Pipe {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+    fn writable(&self) -> bool {
+        self.writable
+    }
+    fn read(&self, buf: UserBuffer) -> usize {
+        assert!(self.readable());
+        let mut bytes = buf.buffers.into_iter().flat_map(|buffer| buffer.iter_mut());
+        let mut read_size = 0;
+        loop {
+            let mut ring = self.buffer.lock();
+            let available = ring.available_read();
+            if available == 0 {
+                if ring.all_write_ends_closed() {
+                    return read_size;
+                }
+                drop(ring);
+                suspend_current_and_run_next();
+                continue;
+            }
+            for _ in 0..available {
+                match bytes.next() {
+                    Some(byte) => *byte = ring.read_byte(),
+                    None => return read_size,
+                }
+                read_size += 1;
+            }
+        }
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        assert!(self.writable());
+        let mut bytes = buf.buffers.into_iter().flat_map(|buffer| buffer.iter());
+        let mut write_size = 0;
+        loop {
+            let mut ring = self.buffer.lock();
+            if ring.all_read_ends_closed() {
+                return write_size;
+            }
+            let available = ring.available_write();
+            if available == 0 {
+                drop(ring);
+                suspend_current_and_run_next();
+                continue;
+            }
+            for _ in 0..available {
+                match bytes.next() {
+                    Some(byte) => ring.write_byte(*byte),
+                    None => return write_size,
+                }
+                write_size += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! The console as stdin, stdout and stderr
+
+use super::File;
+use crate::console::write_bytes;
+use crate::mm::UserBuffer;
+use crate::sbi::console_getchar;
+use crate::task::suspend_current_and_run_next;
+use core::iter::once;
+
+/// console input, one character at a time
+pub struct Stdin;
+
+/// console output
+pub struct Stdout;
+
+/// console output in red, to stand out in the log
+pub struct Stderr;
+
+impl File for Stdin {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    /// Wait for a character and read just that one.
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        if buf.buffers.iter().all(|buffer| buffer.is_empty()) {
+            return 0;
+        }
+        loop {
+            // the legacy SBI call returns -1 while there is no input
+            match console_getchar() as isize {
+                -1 | 0 => suspend_current_and_run_next(),
+                c => return buf.fill_from(&[c as u8]),
+            }
+        }
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+}
+
+impl File for Stdout {
+    fn readable(&self) -> bool {
+        false
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    fn read(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    /// Bytes go out as they are, a UTF-8 character may span two pages.
+    fn write(&self, buf: UserBuffer) -> usize {
+        write_bytes(buf.buffers.iter().map(|buffer| &buffer[..]));
+        buf.buffers.iter().map(|buffer| buffer.len()).sum()
+    }
+}
+
+impl File for Stderr {
+    fn readable(&self) -> bool {
+        false
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    fn read(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn write(&self, buf: UserBuffer) -> usize {
+        let text = buf.buffers.iter().map(|buffer| &buffer[..]);
+        write_bytes(
+            once(&b"\x1b[31m"[..])
+                .chain(text)
+                .chain(once(&b"\x1b[0m"[..])),
+        );
+        buf.buffers.iter().map(|buffer| buffer.len()).sum()
+    }
+}
@@ -12,13 +12,14 @@
 //! initialize various pieces of functionality. (See its source code for
 //! details.)
 //!
-//! We then call [`task::run_first_task()`] and for the first time go to
-//! userspace.
+//! It then starts the other harts, and every hart calls [`task::run_tasks()`]
+//! to go to userspace for the first time.
 
 #![no_std]
 #![no_main]
 #![feature(panic_info_message)]
 #![feature(alloc_error_handler)]
+#![feature(asm_const)]
 
 #[macro_use]
 extern crate bitflags;
@@ -29,7 +30,9 @@ extern crate alloc;
 
 #[macro_use]
 mod console;
+mod board;
 mod config;
+mod fs;
 mod lang_items;
 mod loader;
 mod logging;
@@ -41,7 +44,11 @@ pub mod task;
 mod timer;
 pub mod trap;
 
-core::arch::global_asm!(include_str!("entry.asm"));
+core::arch::global_asm!(
+    include_str!("entry.asm"),
+    max_harts = const config::MAX_HARTS,
+    boot_stack_size = const config::BOOT_STACK_SIZE,
+);
 core::arch::global_asm!(include_str!("link_app.S"));
 
 /// clear BSS segment
@@ -56,19 +63,54 @@ fn clear_bss() {
     }
 }
 
+/// Wake up the other harts in the device tree through the SBI HSM extension.
+/// They start at `_start_secondary` in `entry.asm`.
+fn start_other_harts(boot_hartid: usize) {
+    extern "C" {
+        fn _start_secondary();
+    }
+    let others = board::hart_mask() & !(1 << boot_hartid);
+    for hartid in (0..usize::BITS as usize).filter(|i| others & (1 << i) != 0) {
+        if hartid >= config::MAX_HARTS {
+            warn!("[kernel] hart {} left alone, it is past MAX_HARTS", hartid);
+            continue;
+        }
+        let error = sbi::hart_start(hartid, _start_secondary as usize, 0);
+        if error != 0 {
+            warn!("[kernel] hart {} did not start ({})", hartid, error);
+        }
+    }
+}
+
 #[no_mangle]
-/// the rust entry-point of os
-pub fn rust_main() -> ! {
+/// the rust entry-point of os on the boot hart, `dtb_pa` is the device tree
+/// passed by the SBI
+pub fn rust_main(hartid: usize, dtb_pa: usize) -> ! {
     clear_bss();
     logging::init();
     println!("[kernel] Hello, world!");
+    board::init(dtb_pa);
     mm::init();
     println!("[kernel] back to world!");
     mm::remap_test();
     trap::init();
     //trap::enable_interrupt();
     trap::enable_timer_interrupt();
+    trap::enable_soft_interrupt();
+    timer::set_next_trigger();
+    start_other_harts(hartid);
+    task::run_tasks();
+}
+
+#[no_mangle]
+/// where the harts started by [`start_other_harts`] go, on their own boot
+/// stack. Everything shared is set up by now.
+pub fn rust_main_secondary(hartid: usize) -> ! {
+    mm::KERNEL_SPACE.lock().activate();
+    trap::init();
+    trap::enable_timer_interrupt();
+    trap::enable_soft_interrupt();
     timer::set_next_trigger();
-    task::run_first_task();
-    panic!("Unreachable in rust_main!");
+    info!("[kernel] hart {} is up", hartid);
+    task::run_tasks();
 }
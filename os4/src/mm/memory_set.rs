@@ -4,7 +4,10 @@ use super::{frame_alloc, FrameTracker};
 use super::{PTEFlags, PageTable, PageTableEntry};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use super::{StepByOne, VPNRange};
-use crate::config::{MEMORY_END, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE};
+use crate::board::board_info;
+use crate::config::{
+    MMAP_BASE, PAGE_SIZE, SIGRETURN_TRAMPOLINE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE,
+};
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -23,6 +26,7 @@ extern "C" {
     fn ebss();
     fn ekernel();
     fn strampoline();
+    fn ssigreturn();
 }
 
 // create a global inistance of Kernel address space.
@@ -65,6 +69,18 @@ impl MemorySet {
             None,
         );
     }
+    /// Like [`Self::insert_framed_area`], but the frames stay shared with
+    /// the child when the address space is copied by `fork`.
+    pub fn insert_shared_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) {
+        let mut map_area = MapArea::new(start_va, end_va, MapType::Framed, permission);
+        map_area.shared = true;
+        self.push(map_area, None);
+    }
     // push a new logical segment map_area ( if it's using a MapType::Framed to map to PhysAddr, then he could also choice to write something in frame)
     fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
         map_area.map(&mut self.page_table);
@@ -93,12 +109,40 @@ impl MemorySet {
         false
 
     }
+    /// Unmap and drop the area starting at `start_vpn`, if there is one.
+    pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
+        if let Some((idx, area)) = self
+            .areas
+            .iter_mut()
+            .enumerate()
+            .find(|(_, area)| area.vpn_range.get_start() == start_vpn)
+        {
+            area.unmap(&mut self.page_table);
+            self.areas.remove(idx);
+        }
+    }
+    /// Free all areas and page-table frames except the root, leaving an empty
+    /// address space behind. Used to release an exited task's memory early.
+    pub fn recycle_data_pages(&mut self) {
+        self.areas.clear();
+        self.page_table.clear();
+    }
     /// Mention that trampoline is not collected by areas.
+    /// It is the same in every address space, so it is mapped global.
     fn map_trampoline(&mut self) {
         self.page_table.map(
             VirtAddr::from(TRAMPOLINE).into(),
             PhysAddr::from(strampoline as usize).into(),
-            PTEFlags::R | PTEFlags::X,
+            PTEFlags::R | PTEFlags::X | PTEFlags::G,
+        );
+    }
+    /// The code signal handlers return to, readable and executable from user
+    /// mode. Like the trampoline it is not collected by areas.
+    fn map_sigreturn_trampoline(&mut self) {
+        self.page_table.map(
+            VirtAddr::from(SIGRETURN_TRAMPOLINE).into(),
+            PhysAddr::from(ssigreturn as usize).into(),
+            PTEFlags::R | PTEFlags::X | PTEFlags::U,
         );
     }
     // create address space of kernel
@@ -155,16 +199,29 @@ impl MemorySet {
             ),
             None,
         );
+        let board = board_info();
         info!("mapping physical memory");
         memory_set.push(
             MapArea::new(
                 (ekernel as usize).into(),
-                MEMORY_END.into(),
+                board.memory_end.into(),
                 MapType::Identical,
                 MapPermission::R | MapPermission::W,
             ),
             None,
         );
+        info!("mapping memory-mapped registers");
+        for region in board.mmio_regions() {
+            memory_set.push(
+                MapArea::new(
+                    region.base.into(),
+                    (region.base + region.size).into(),
+                    MapType::Identical,
+                    MapPermission::R | MapPermission::W,
+                ),
+                None,
+            );
+        }
         memory_set
     }
     /// Include sections in elf and trampoline and TrapContext and user stack,
@@ -173,6 +230,7 @@ impl MemorySet {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
+        memory_set.map_sigreturn_trampoline();
         // map program headers of elf, with U flag
         let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
         let elf_header = elf.header;
@@ -235,6 +293,46 @@ impl MemorySet {
             elf.header.pt2.entry_point() as usize,
         )
     }
+    /// The areas of this user address space, holding on to their frames.
+    pub fn snapshot(&self) -> MemorySnapshot {
+        MemorySnapshot(
+            self.areas
+                .iter()
+                .map(|area| MapArea {
+                    vpn_range: area.vpn_range,
+                    data_frames: area.data_frames.clone(),
+                    map_type: area.map_type,
+                    map_perm: area.map_perm,
+                    shared: area.shared,
+                })
+                .collect(),
+        )
+    }
+    /// Copy the address space of a user task for `fork`. Shared areas are
+    /// mapped onto the very same frames, everything else is copied.
+    pub fn from_existed_user(user_space: &MemorySnapshot) -> MemorySet {
+        let mut memory_set = Self::new_bare();
+        // map trampoline
+        memory_set.map_trampoline();
+        memory_set.map_sigreturn_trampoline();
+        for area in user_space.0.iter() {
+            if area.shared {
+                let new_area = area.share(&mut memory_set.page_table);
+                memory_set.areas.push(new_area);
+                continue;
+            }
+            let new_area = MapArea::from_another(area);
+            memory_set.push(new_area, None);
+            // copy data from another space
+            for (vpn, src) in area.data_frames.iter() {
+                let dst_ppn = memory_set.translate(*vpn).unwrap().ppn();
+                dst_ppn
+                    .get_bytes_array()
+                    .copy_from_slice(src.ppn.get_bytes_array());
+            }
+        }
+        memory_set
+    }
     pub fn activate(&self) {
         let satp = self.page_table.token();
         unsafe {
@@ -245,16 +343,44 @@ impl MemorySet {
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
     }
-    // check if all his MapArea has been map
-    pub fn is_all_map(&self, vpn: VirtPageNum) -> bool{
-        for item in self.areas.iter() {
-            if item.is_map(vpn) {
-                return true
-            }
+    /// start, end and permissions of every area, in the order they were
+    /// added
+    pub fn areas(&self) -> impl Iterator<Item = (VirtAddr, VirtAddr, MapPermission)> + '_ {
+        self.areas.iter().map(|area| {
+            (
+                area.vpn_range.get_start().into(),
+                area.vpn_range.get_end().into(),
+                area.map_perm,
+            )
+        })
+    }
+    /// Copy user memory at `va` into `buf`. Unlike the `translated_*`
+    /// helpers this does not trust the address: it fails, rather than
+    /// panics, unless every byte lies in a mapped area with `U` set.
+    pub fn read_user(&self, va: usize, buf: &mut [u8]) -> bool {
+        let end = match va.checked_add(buf.len()) {
+            Some(end) => end,
+            None => return false,
+        };
+        let mut start = va;
+        while start < end {
+            let vpn = VirtAddr::from(start).floor();
+            let in_user_area = self
+                .areas
+                .iter()
+                .any(|area| area.map_perm.contains(MapPermission::U) && area.is_map(vpn));
+            let ppn = match self.page_table.translate(vpn) {
+                Some(pte) if in_user_area && pte.is_valid() => pte.ppn(),
+                _ => return false,
+            };
+            let offset = start % PAGE_SIZE;
+            let len = (PAGE_SIZE - offset).min(end - start);
+            buf[start - va..start - va + len]
+                .copy_from_slice(&ppn.get_bytes_array()[offset..offset + len]);
+            start += len;
         }
-        false
+        true
     }
-
     pub fn is_not_all_map(&self, vpn: VirtPageNum) -> bool {
         for item in self.areas.iter() {
             if item.is_map(vpn) {
@@ -263,15 +389,71 @@ impl MemorySet {
         }
         true
     }
+    /// Whether `[start_va, end_va)` may be used for a new user mapping: it
+    /// must be non-empty, stay below `SIGRETURN_TRAMPOLINE` in the user half
+    /// of Sv39 (so it can never touch `TRAP_CONTEXT` or `TRAMPOLINE`) and
+    /// overlap no existing area.
+    pub fn is_free_user_range(&self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        if start_va >= end_va || end_va.0 > SIGRETURN_TRAMPOLINE {
+            return false;
+        }
+        let (start_vpn, end_vpn) = (start_va.floor(), end_va.ceil());
+        !self
+            .areas
+            .iter()
+            .any(|area| area.overlaps(start_vpn, end_vpn))
+    }
+    /// Find the lowest `align`-aligned gap of `len` bytes above `MMAP_BASE`
+    /// that [`Self::is_free_user_range`] would accept.
+    pub fn find_free_range(&self, len: usize, align: usize) -> Option<VirtAddr> {
+        assert!(align.is_power_of_two() && align >= PAGE_SIZE);
+        let len = align_up(len, PAGE_SIZE)?;
+        let mut used: Vec<(usize, usize)> = self
+            .areas
+            .iter()
+            .map(|area| {
+                let start: VirtAddr = area.vpn_range.get_start().into();
+                let end: VirtAddr = area.vpn_range.get_end().into();
+                (start.0, end.0)
+            })
+            .collect();
+        used.sort_unstable();
+        let mut start = align_up(MMAP_BASE, align)?;
+        for (area_start, area_end) in used {
+            if start.checked_add(len)? <= area_start {
+                break;
+            }
+            if area_end > start {
+                start = align_up(area_end, align)?;
+            }
+        }
+        let end = start.checked_add(len)?;
+        if len == 0 || end > SIGRETURN_TRAMPOLINE {
+            return None;
+        }
+        Some(start.into())
+    }
 }
 
+/// round `v` up to a multiple of `align` (a power of two), `None` on overflow
+fn align_up(v: usize, align: usize) -> Option<usize> {
+    Some(v.checked_add(align - 1)? & !(align - 1))
+}
+
+/// The areas of a user address space along with their frames, which stay
+/// alive as long as this does. `fork` copies the parent from it, so that the
+/// parent need not be borrowed meanwhile.
+pub struct MemorySnapshot(Vec<MapArea>);
+
 // using for describute logically in segments ( which contains a kinds of .bss, .data and so on. )
 /// map area structure, controls a contiguous piece of virtual memory
 pub struct MapArea {
     vpn_range: VPNRange,                                // describe a continuity interval of VPN [成段的连续的VPN] 
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    data_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>,
     map_type: MapType,
     map_perm: MapPermission,
+    /// frames are shared with, rather than copied into, forked children
+    shared: bool,
 }
 
 impl MapArea {
@@ -288,8 +470,46 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            shared: false,
         }
     }
+    /// an unmapped area with the same range and permissions as `another`
+    pub fn from_another(another: &MapArea) -> Self {
+        Self {
+            vpn_range: VPNRange::new(another.vpn_range.get_start(), another.vpn_range.get_end()),
+            data_frames: BTreeMap::new(),
+            map_type: another.map_type,
+            map_perm: another.map_perm,
+            shared: another.shared,
+        }
+    }
+    /// A copy of this area mapped into `page_table` on the same frames.
+    fn share(&self, page_table: &mut PageTable) -> MapArea {
+        let pte_flags = self.pte_flags();
+        for (vpn, frame) in self.data_frames.iter() {
+            page_table.map(*vpn, frame.ppn, pte_flags);
+        }
+        Self {
+            vpn_range: self.vpn_range,
+            data_frames: self.data_frames.clone(),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+            shared: true,
+        }
+    }
+    fn pte_flags(&self) -> PTEFlags {
+        let mut pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        // user pages belong to a single address space, never global
+        debug_assert!(
+            !pte_flags.contains(PTEFlags::U | PTEFlags::G),
+            "user area at {:?} must not be global",
+            self.vpn_range.get_start()
+        );
+        if pte_flags.contains(PTEFlags::U) {
+            pte_flags.remove(PTEFlags::G);
+        }
+        pte_flags
+    }
     // TODO haven't understand
     pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         let ppn: PhysPageNum;
@@ -300,11 +520,10 @@ impl MapArea {
             MapType::Framed => {
                 let frame = frame_alloc().unwrap();
                 ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+                self.data_frames.insert(vpn, Arc::new(frame));
             }
         }
-        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+        page_table.map(vpn, ppn, self.pte_flags());
     }
     #[allow(unused)]
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
@@ -359,6 +578,10 @@ impl MapArea {
     pub fn is_map(&self, vpn:VirtPageNum) -> bool {
         return self.vpn_range.get_start().0 <= vpn.0 && self.vpn_range.get_end().0 > vpn.0;
     }
+    /// Whether this area shares a page with `[start, end)`.
+    pub fn overlaps(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        self.vpn_range.get_start() < end && start < self.vpn_range.get_end()
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
@@ -371,12 +594,14 @@ pub enum MapType {
 
 // packaging a set of bool to a u8
 bitflags! {
-    /// map permission corresponding to that in pte: `R W X U`
+    /// map permission corresponding to that in pte: `R W X U G`
     pub struct MapPermission: u8 {
         const R = 1 << 1;
         const W = 1 << 2;
         const X = 1 << 3;
         const U = 1 << 4;
+        /// present in every address space; not allowed together with `U`
+        const G = 1 << 5;
     }
 }
 
@@ -401,5 +626,10 @@ pub fn remap_test() {
         .translate(mid_data.floor())
         .unwrap()
         .executable());
+    assert!(kernel_space
+        .page_table
+        .translate(VirtAddr::from(TRAMPOLINE).floor())
+        .unwrap()
+        .is_global());
     info!("remap_test passed!");
 }
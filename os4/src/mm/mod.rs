@@ -17,9 +17,10 @@ pub use address::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use address::{StepByOne, VPNRange};
 pub use frame_allocator::{frame_alloc, FrameTracker};
 pub use memory_set::remap_test;
-pub use memory_set::{MapPermission, MemorySet, KERNEL_SPACE};
-pub use page_table::{translated_byte_buffer, PageTableEntry};
+pub use memory_set::{MapPermission, MemorySet, MemorySnapshot, KERNEL_SPACE};
+pub use page_table::{copy_to_user, translated_byte_buffer, translated_refmut, translated_str};
 use page_table::{PTEFlags, PageTable};
+pub use page_table::{PageTableEntry, UserBuffer};
 
 /// initiate heap allocator, frame allocator and kernel space
 pub fn init() {
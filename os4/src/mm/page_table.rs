@@ -1,6 +1,8 @@
 //! Implementation of [`PageTableEntry`] and [`PageTable`].
 
 use super::{frame_alloc, FrameTracker, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
+use crate::config::PAGE_SIZE;
+use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 use bitflags::*;
@@ -59,6 +61,13 @@ impl PageTableEntry {
     pub fn executable(&self) -> bool {
         (self.flags() & PTEFlags::X) != PTEFlags::empty()
     }
+    pub fn is_global(&self) -> bool {
+        (self.flags() & PTEFlags::G) != PTEFlags::empty()
+    }
+    /// valid and accessible from user mode
+    pub fn is_user(&self) -> bool {
+        self.is_valid() && self.flags().contains(PTEFlags::U)
+    }
 }
 
 /*  page table structure
@@ -143,6 +152,13 @@ impl PageTable {
         assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
         *pte = PageTableEntry::empty();
     }
+    /// Drop every mapping and free all frames but the root table.
+    pub fn clear(&mut self) {
+        for pte in self.root_ppn.get_pte_array() {
+            *pte = PageTableEntry::empty();
+        }
+        self.frames.truncate(1);
+    }
     // if could `find_pte` then copy VirtPageNum and return else just return None
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.find_pte(vpn).copied()
@@ -153,16 +169,24 @@ impl PageTable {
     }
 }
 
-/// translate a pointer to a mutable u8 Vec through page table
-pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
+/// Translate the user buffer of `len` bytes at `ptr` into the pieces it has
+/// in each page, `None` unless every page is mapped for user code.
+pub fn translated_byte_buffer(
+    token: usize,
+    ptr: *const u8,
+    len: usize,
+) -> Option<Vec<&'static mut [u8]>> {
     let page_table = PageTable::from_token(token);
     let mut start = ptr as usize;
-    let end = start + len;
+    let end = start.checked_add(len)?;
     let mut v = Vec::new();
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn = start_va.floor();
-        let ppn = page_table.translate(vpn).unwrap().ppn();
+        let ppn = page_table
+            .translate(vpn)
+            .filter(PageTableEntry::is_user)?
+            .ppn();
         vpn.step();
         let mut end_va: VirtAddr = vpn.into();
         end_va = end_va.min(VirtAddr::from(end));
@@ -173,26 +197,81 @@ pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&
         }
         start = end_va.into();
     }
-    v
+    Some(v)
+}
+
+/// a buffer in user space, as the pieces it has in each page
+pub struct UserBuffer {
+    pub buffers: Vec<&'static mut [u8]>,
+}
+
+impl UserBuffer {
+    pub fn new(buffers: Vec<&'static mut [u8]>) -> Self {
+        Self { buffers }
+    }
+    /// Copy as much of `data` as fits, returns the number of bytes copied.
+    pub fn fill_from(&mut self, data: &[u8]) -> usize {
+        let mut copied = 0;
+        for buffer in self.buffers.iter_mut() {
+            let n = buffer.len().min(data.len() - copied);
+            buffer[..n].copy_from_slice(&data[copied..copied + n]);
+            copied += n;
+        }
+        copied
+    }
+    /// the contents, copied into the kernel
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.buffers.concat()
+    }
 }
 
+/// Translate user address `p` of the address space `token` into a physical
+/// address, `None` unless its page is mapped for user code.
+pub fn get_phy_addr(token: usize, p: usize) -> Option<usize> {
+    let page_table = PageTable::from_token(token);
+    let virt_addr = VirtAddr(p);
+    let pte = page_table
+        .translate(virt_addr.floor())
+        .filter(PageTableEntry::is_user)?;
+    Some(pte.ppn().0 << 12 | virt_addr.page_offset())
+}
 
-// public function:
-// pub fn get_phyAddr(token: usize, p: &mut syscall::process ) {
+/// Load a `\0`-terminated string from a user address space, without the
+/// `\0`. `None` if it runs into memory user code may not access.
+pub fn translated_str(token: usize, ptr: *const u8) -> Option<String> {
+    let mut string = String::new();
+    let mut va = ptr as usize;
+    loop {
+        let ch = unsafe { *(get_phy_addr(token, va)? as *const u8) };
+        if ch == 0 {
+            break;
+        }
+        string.push(ch as char);
+        va = va.checked_add(1)?;
+    }
+    Some(string)
+}
 
-// }
+/// Translate a user pointer to a mutable reference in kernel space. `None`
+/// unless it is aligned, in a page mapped for user code and does not cross
+/// into the next page; see [`copy_to_user`] for larger objects.
+pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> Option<&'static mut T> {
+    let va = ptr as usize;
+    if va % core::mem::align_of::<T>() != 0
+        || va % PAGE_SIZE + core::mem::size_of::<T>() > PAGE_SIZE
+    {
+        return None;
+    }
+    unsafe { (get_phy_addr(token, va)? as *mut T).as_mut() }
+}
 
-// #[allow(unused, dead_code)]
-// token: the address of current user
-// p: a address of pointer 
-// we changing the address of pointer to a frame 
-pub fn get_phy_addr(token: usize, p: usize) -> usize{
-    let page_table = PageTable::from_token(token);           // get page table 
-    let virt_addr = VirtAddr(p);                                   // get VA from pointer
-    let vpn = virt_addr.floor();                                // get VPN
-    let ppn = page_table                              //   
-                                        .translate(vpn)// using page table and VPN to find PPN
-                                        .unwrap()                       
-                                        .ppn();
-    ppn.0 << 12 | virt_addr.page_offset() as usize
-}
\ No newline at end of file
+/// Copy `value` to the user address `ptr`, which may cross pages. `None`,
+/// with nothing copied, unless all of it is mapped for user code.
+pub fn copy_to_user<T>(token: usize, ptr: *mut T, value: &T) -> Option<()> {
+    let bytes = unsafe {
+        core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>())
+    };
+    let buffers = translated_byte_buffer(token, ptr as *const u8, bytes.len())?;
+    UserBuffer::new(buffers).fill_from(bytes);
+    Some(())
+}
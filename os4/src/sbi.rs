@@ -2,11 +2,36 @@
 
 #![allow(unused)]
 
+use crate::board::{board_info, MmioKind};
+
 const SBI_SET_TIMER: usize = 0;
 const SBI_CONSOLE_PUTCHAR: usize = 1;
 const SBI_CONSOLE_GETCHAR: usize = 2;
 const SBI_SHUTDOWN: usize = 8;
 
+/// System Reset extension, SBI v0.3
+const SBI_EXT_SRST: usize = 0x5352_5354;
+const SBI_SRST_RESET: usize = 0;
+const SBI_SRST_TYPE_SHUTDOWN: usize = 0;
+const SBI_SRST_REASON_NONE: usize = 0;
+const SBI_SRST_REASON_SYSTEM_FAILURE: usize = 1;
+
+/// Hart State Management extension, SBI v0.2
+const SBI_EXT_HSM: usize = 0x48_534D;
+const SBI_HSM_HART_START: usize = 0;
+
+/// IPI extension, SBI v0.2
+const SBI_EXT_IPI: usize = 0x73_5049;
+const SBI_IPI_SEND_IPI: usize = 0;
+
+/// Remote Fence extension, SBI v0.2
+const SBI_EXT_RFENCE: usize = 0x5246_4E43;
+const SBI_RFENCE_REMOTE_SFENCE_VMA: usize = 1;
+
+/// values for QEMU's sifive_test device, the exit code goes in the upper half
+const SIFIVE_TEST_PASS: u32 = 0x5555;
+const SIFIVE_TEST_FAIL: u32 = 0x3333;
+
 #[inline(always)]
 /// general sbi call
 fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
@@ -23,6 +48,40 @@ fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     ret
 }
 
+#[inline(always)]
+/// [`sbi_call_ext`] for functions that take up to four arguments
+fn sbi_call_ext4(eid: usize, fid: usize, args: [usize; 4]) -> (isize, usize) {
+    let (error, value);
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("x10") args[0] => error,
+            inlateout("x11") args[1] => value,
+            in("x12") args[2],
+            in("x13") args[3],
+            in("x16") fid,
+            in("x17") eid,
+        );
+    }
+    (error, value)
+}
+
+#[inline(always)]
+/// sbi call to function `fid` of extension `eid`, returns (error, value)
+fn sbi_call_ext(eid: usize, fid: usize, arg0: usize, arg1: usize) -> (isize, usize) {
+    let (error, value);
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("x10") arg0 => error,
+            inlateout("x11") arg1 => value,
+            in("x16") fid,
+            in("x17") eid,
+        );
+    }
+    (error, value)
+}
+
 /// use sbi call to set timer
 pub fn set_timer(timer: usize) {
     sbi_call(SBI_SET_TIMER, timer, 0, 0);
@@ -38,8 +97,54 @@ pub fn console_getchar() -> usize {
     sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0)
 }
 
-/// use sbi call to shutdown the kernel
-pub fn shutdown() -> ! {
+/// Start the stopped hart `hartid` in supervisor mode at the physical
+/// address `start_addr`, with `a0 = hartid` and `a1 = opaque`. Returns the
+/// SBI error code, 0 on success.
+pub fn hart_start(hartid: usize, start_addr: usize, opaque: usize) -> isize {
+    let args = [hartid, start_addr, opaque, 0];
+    sbi_call_ext4(SBI_EXT_HSM, SBI_HSM_HART_START, args).0
+}
+
+/// Raise a supervisor software interrupt on the harts in `hart_mask`.
+/// Returns the SBI error code.
+pub fn send_ipi(hart_mask: usize) -> isize {
+    sbi_call_ext(SBI_EXT_IPI, SBI_IPI_SEND_IPI, hart_mask, 0).0
+}
+
+/// Have the harts in `hart_mask` flush their TLB for `[start, start + size)`
+/// of the address space they are in. Returns the SBI error code.
+pub fn remote_sfence_vma(hart_mask: usize, start: usize, size: usize) -> isize {
+    let hart_mask_base = 0;
+    sbi_call_ext4(
+        SBI_EXT_RFENCE,
+        SBI_RFENCE_REMOTE_SFENCE_VMA,
+        [hart_mask, hart_mask_base, start, size],
+    )
+    .0
+}
+
+/// Power off, reporting `failure` to whoever started us.
+///
+/// SBI SRST is tried first. Without it we poke QEMU's sifive_test device if
+/// the device tree has one, and the legacy SBI shutdown, which cannot carry a
+/// status, is the last resort.
+pub fn shutdown(failure: bool) -> ! {
+    let reason = if failure {
+        SBI_SRST_REASON_SYSTEM_FAILURE
+    } else {
+        SBI_SRST_REASON_NONE
+    };
+    sbi_call_ext(SBI_EXT_SRST, SBI_SRST_RESET, SBI_SRST_TYPE_SHUTDOWN, reason);
+    if let Some(test) = board_info().mmio_regions().find(|r| r.kind == MmioKind::Test) {
+        let value = if failure {
+            (1 << 16) | SIFIVE_TEST_FAIL
+        } else {
+            SIFIVE_TEST_PASS
+        };
+        unsafe {
+            (test.base as *mut u32).write_volatile(value);
+        }
+    }
     sbi_call(SBI_SHUTDOWN, 0, 0, 0);
     panic!("It should shutdown!");
 }
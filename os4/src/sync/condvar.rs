@@ -0,0 +1,60 @@
+//! Condition variables for user programs
+
+use super::SpinLock;
+use crate::task::{block_current_and_run_next, current_task_id, wakeup_task};
+use alloc::collections::VecDeque;
+
+pub struct Condvar {
+    pub inner: SpinLock<CondvarInner>,
+}
+
+pub struct CondvarInner {
+    /// task ids of the waiters, in the order they came
+    pub wait_queue: VecDeque<usize>,
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinLock::new(CondvarInner {
+                wait_queue: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Wake up the first waiter, if any. Signals nobody waits for are lost.
+    pub fn signal(&self) {
+        let waiter = self.inner.lock().wait_queue.pop_front();
+        if let Some(waiter) = waiter {
+            wakeup_task(waiter);
+        }
+    }
+
+    /// Stop task `id` from waiting, e.g. it is being killed.
+    pub fn cancel_wait(&self, id: usize) {
+        self.inner.lock().wait_queue.retain(|&waiter| waiter != id);
+    }
+
+    /// Block until signalled. `release` lets go of the caller's mutex once
+    /// we are queued, so a signal sent by whoever takes it next is not lost.
+    /// The caller takes the mutex again after.
+    ///
+    /// Returns `false` without waiting if `release` fails.
+    pub fn wait(&self, release: impl FnOnce() -> bool) -> bool {
+        let id = current_task_id();
+        self.inner.lock().wait_queue.push_back(id);
+        if !release() {
+            self.cancel_wait(id);
+            return false;
+        }
+        // a wakeup that comes before we block is kept as `wakeup_pending`
+        block_current_and_run_next();
+        true
+    }
+}
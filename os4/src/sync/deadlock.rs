@@ -0,0 +1,136 @@
+//! Deadlock detection for the mutexes and semaphores of a process
+//!
+//! Every mutex and semaphore is a resource type, with one unit per mutex and
+//! as many as the semaphore starts with. We keep, per thread, how many units
+//! of each it holds and how many it waits for, and before a thread blocks
+//! run the banker's safety check: can the threads finish in some order,
+//! each one getting what it waits for from what is free plus what the
+//! threads before it released? If not, blocking would deadlock.
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// a resource type, by the id user programs know it by
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Resource {
+    Mutex(usize),
+    Semaphore(usize),
+}
+
+/// resource accounting of one process, threads indexed by tid
+#[derive(Default)]
+pub struct DeadlockDetector {
+    /// whether [`DeadlockDetector::request`] runs the safety check
+    pub enabled: bool,
+    /// column of each resource type in the vectors and matrices below
+    columns: BTreeMap<Resource, usize>,
+    /// free units of each resource type
+    available: Vec<usize>,
+    /// units each thread holds
+    allocation: Vec<Vec<usize>>,
+    /// units each thread waits for
+    need: Vec<Vec<usize>>,
+}
+
+/// the entry of `matrix` for thread `tid` and resource `column`, grown as
+/// needed
+fn cell(matrix: &mut Vec<Vec<usize>>, tid: usize, column: usize) -> &mut usize {
+    if tid >= matrix.len() {
+        matrix.resize(tid + 1, Vec::new());
+    }
+    let row = &mut matrix[tid];
+    if column >= row.len() {
+        row.resize(column + 1, 0);
+    }
+    &mut row[column]
+}
+
+impl DeadlockDetector {
+    /// Start tracking `resource`, which has `units` free units.
+    pub fn add_resource(&mut self, resource: Resource, units: usize) {
+        let column = self.available.len();
+        self.available.push(units);
+        self.columns.insert(resource, column);
+    }
+
+    fn column(&self, resource: Resource) -> usize {
+        self.columns[&resource]
+    }
+
+    /// Thread `tid` is about to block waiting for a unit of `resource`.
+    /// Returns false, and forgets the request, if that would deadlock.
+    pub fn request(&mut self, tid: usize, resource: Resource) -> bool {
+        let column = self.column(resource);
+        *cell(&mut self.need, tid, column) += 1;
+        if self.enabled && !self.is_safe() {
+            *cell(&mut self.need, tid, column) -= 1;
+            return false;
+        }
+        true
+    }
+
+    /// Thread `tid` took a free unit of `resource`, maybe after waiting for
+    /// it to become free.
+    pub fn acquire(&mut self, tid: usize, resource: Resource) {
+        let column = self.column(resource);
+        let need = cell(&mut self.need, tid, column);
+        *need = need.saturating_sub(1);
+        self.available[column] -= 1;
+        *cell(&mut self.allocation, tid, column) += 1;
+    }
+
+    /// Thread `tid` gave back a unit of `resource`, which went directly to
+    /// the waiting thread `handed_to` if there is one. A semaphore may be
+    /// released by a thread that never took it.
+    pub fn release(&mut self, tid: usize, resource: Resource, handed_to: Option<usize>) {
+        let column = self.column(resource);
+        let allocation = cell(&mut self.allocation, tid, column);
+        *allocation = allocation.saturating_sub(1);
+        match handed_to {
+            Some(waiter) => {
+                let need = cell(&mut self.need, waiter, column);
+                *need = need.saturating_sub(1);
+                *cell(&mut self.allocation, waiter, column) += 1;
+            }
+            None => self.available[column] += 1,
+        }
+    }
+
+    /// Forget the reaped thread `tid`, its tid may be handed out again.
+    pub fn remove_thread(&mut self, tid: usize) {
+        for matrix in [&mut self.allocation, &mut self.need] {
+            if let Some(row) = matrix.get_mut(tid) {
+                row.clear();
+            }
+        }
+    }
+
+    /// the banker's safety check
+    fn is_safe(&self) -> bool {
+        let mut work = self.available.clone();
+        let mut finish = vec![false; self.need.len().max(self.allocation.len())];
+        let entry = |matrix: &Vec<Vec<usize>>, tid: usize, column: usize| {
+            matrix
+                .get(tid)
+                .and_then(|row| row.get(column))
+                .copied()
+                .unwrap_or(0)
+        };
+        loop {
+            let next = (0..finish.len()).find(|&tid| {
+                !finish[tid]
+                    && (0..work.len()).all(|column| entry(&self.need, tid, column) <= work[column])
+            });
+            match next {
+                Some(tid) => {
+                    for (column, units) in work.iter_mut().enumerate() {
+                        *units += entry(&self.allocation, tid, column);
+                    }
+                    finish[tid] = true;
+                }
+                None => return finish.iter().all(|done| *done),
+            }
+        }
+    }
+}
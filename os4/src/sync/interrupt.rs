@@ -0,0 +1,53 @@
+//! Nesting of interrupt-disabled sections
+//!
+//! Every [`UPSafeCell`](super::UPSafeCell) borrow and [`SpinLock`](super::SpinLock)
+//! holds interrupts off on its hart: a handler that comes in while we hold
+//! one could want it too. The sections nest, interrupts are back on only
+//! when the outermost one ends, and only if they were on when it began.
+
+use crate::config::MAX_HARTS;
+use crate::task::hart_id;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use riscv::register::sstatus;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const OFF: AtomicBool = AtomicBool::new(false);
+
+/// how deep each hart is in [`push_off`]
+static NOFF: [AtomicUsize; MAX_HARTS] = [ZERO; MAX_HARTS];
+/// whether interrupts were enabled before the outermost [`push_off`]
+static INTENA: [AtomicBool; MAX_HARTS] = [OFF; MAX_HARTS];
+
+/// Disable interrupts on this hart, remembering whether they were enabled
+/// if this is the outermost section.
+pub fn push_off() {
+    let enabled = sstatus::read().sie();
+    unsafe {
+        sstatus::clear_sie();
+    }
+    let hart = hart_id();
+    if NOFF[hart].fetch_add(1, Ordering::Relaxed) == 0 {
+        INTENA[hart].store(enabled, Ordering::Relaxed);
+    }
+}
+
+/// End the section [`push_off`] began, enabling interrupts again when the
+/// outermost one ends and they were enabled before it.
+pub fn pop_off() {
+    assert!(!sstatus::read().sie(), "pop_off with interrupts enabled");
+    let hart = hart_id();
+    let depth = NOFF[hart].fetch_sub(1, Ordering::Relaxed);
+    assert!(depth > 0, "pop_off without push_off");
+    if depth == 1 && INTENA[hart].load(Ordering::Relaxed) {
+        unsafe {
+            sstatus::set_sie();
+        }
+    }
+}
+
+/// how many sections this hart is in, 0 if none
+pub fn depth() -> usize {
+    NOFF[hart_id()].load(Ordering::Relaxed)
+}
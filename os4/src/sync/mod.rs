@@ -1,5 +1,17 @@
 //! Synchronization and interior mutability primitives
 
+mod condvar;
+mod deadlock;
+mod interrupt;
+mod mutex;
+mod semaphore;
+mod spinlock;
 mod up;
 
-pub use up::UPSafeCell;
+pub use condvar::Condvar;
+pub use deadlock::{DeadlockDetector, Resource};
+pub use interrupt::{depth as interrupt_depth, pop_off, push_off};
+pub use mutex::{Mutex, MutexBlocking, MutexSpin};
+pub use semaphore::Semaphore;
+pub use spinlock::{SpinLock, SpinLockGuard};
+pub use up::{UPSafeCell, UPSafeCellGuard};
@@ -0,0 +1,129 @@
+//! Mutexes for user programs
+//!
+//! A spin mutex yields the CPU while the lock is taken and tries again when
+//! it is scheduled. A blocking mutex queues its waiters and hands the lock
+//! directly to the first one on unlock.
+
+use super::SpinLock;
+use crate::task::wakeup_task;
+use crate::task::{block_current_and_run_next, current_task_id, suspend_current_and_run_next};
+use alloc::collections::VecDeque;
+
+/// `unlock` of a mutex the current task does not hold
+#[derive(Debug)]
+pub struct NotOwner;
+
+/// a mutex of a process, shared by its threads
+pub trait Mutex: Sync + Send {
+    /// Returns whether we waited and `unlock` handed the lock to us.
+    fn lock(&self) -> bool;
+    /// Returns the task id of the waiter the lock was handed to, if any.
+    fn unlock(&self) -> Result<Option<usize>, NotOwner>;
+    fn is_locked(&self) -> bool;
+    /// Stop task `id` from waiting, it is being killed.
+    fn cancel_wait(&self, _id: usize) {}
+}
+
+/// mutex that lets the other tasks run while it waits
+pub struct MutexSpin {
+    /// task id of the holder
+    owner: SpinLock<Option<usize>>,
+}
+
+impl MutexSpin {
+    pub fn new() -> Self {
+        Self {
+            owner: SpinLock::new(None),
+        }
+    }
+}
+
+impl Mutex for MutexSpin {
+    fn lock(&self) -> bool {
+        loop {
+            let mut owner = self.owner.lock();
+            if owner.is_some() {
+                drop(owner);
+                suspend_current_and_run_next();
+                continue;
+            } else {
+                *owner = Some(current_task_id());
+                return false;
+            }
+        }
+    }
+
+    fn unlock(&self) -> Result<Option<usize>, NotOwner> {
+        let mut owner = self.owner.lock();
+        if *owner != Some(current_task_id()) {
+            return Err(NotOwner);
+        }
+        *owner = None;
+        Ok(None)
+    }
+
+    fn is_locked(&self) -> bool {
+        self.owner.lock().is_some()
+    }
+}
+
+/// mutex whose waiters are `Blocked` until it is their turn
+pub struct MutexBlocking {
+    inner: SpinLock<MutexBlockingInner>,
+}
+
+pub struct MutexBlockingInner {
+    /// task id of the holder
+    owner: Option<usize>,
+    /// task ids of the waiters, in the order they came
+    wait_queue: VecDeque<usize>,
+}
+
+impl MutexBlocking {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinLock::new(MutexBlockingInner {
+                owner: None,
+                wait_queue: VecDeque::new(),
+            }),
+        }
+    }
+}
+
+impl Mutex for MutexBlocking {
+    fn lock(&self) -> bool {
+        let mut inner = self.inner.lock();
+        if inner.owner.is_some() {
+            inner.wait_queue.push_back(current_task_id());
+            drop(inner);
+            // the lock is ours once we are woken up
+            block_current_and_run_next();
+            true
+        } else {
+            inner.owner = Some(current_task_id());
+            false
+        }
+    }
+
+    fn unlock(&self) -> Result<Option<usize>, NotOwner> {
+        let mut inner = self.inner.lock();
+        if inner.owner != Some(current_task_id()) {
+            return Err(NotOwner);
+        }
+        let waiter = inner.wait_queue.pop_front();
+        inner.owner = waiter;
+        drop(inner);
+        if let Some(waiter) = waiter {
+            wakeup_task(waiter);
+        }
+        Ok(waiter)
+    }
+
+    fn is_locked(&self) -> bool {
+        self.inner.lock().owner.is_some()
+    }
+
+    fn cancel_wait(&self, id: usize) {
+        self.inner.lock().wait_queue.retain(|&waiter| waiter != id);
+    }
+}
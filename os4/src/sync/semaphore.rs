@@ -0,0 +1,73 @@
+//! Counting semaphores for user programs
+
+use super::SpinLock;
+use crate::task::{block_current_and_run_next, current_task_id, wakeup_task};
+use alloc::collections::VecDeque;
+
+pub struct Semaphore {
+    pub inner: SpinLock<SemaphoreInner>,
+}
+
+pub struct SemaphoreInner {
+    /// free resources, or minus the number of waiters
+    pub count: isize,
+    /// task ids of the waiters, in the order they came
+    pub wait_queue: VecDeque<usize>,
+}
+
+impl Semaphore {
+    pub fn new(res_count: usize) -> Self {
+        Self {
+            inner: SpinLock::new(SemaphoreInner {
+                count: res_count as isize,
+                wait_queue: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Returns the task id of the waiter the resource was handed to, if any.
+    pub fn up(&self) -> Option<usize> {
+        let mut inner = self.inner.lock();
+        inner.count += 1;
+        if inner.count > 0 {
+            return None;
+        }
+        let waiter = inner.wait_queue.pop_front();
+        drop(inner);
+        if let Some(waiter) = waiter {
+            wakeup_task(waiter);
+        }
+        waiter
+    }
+
+    /// Returns whether we waited and `up` handed the resource to us.
+    pub fn down(&self) -> bool {
+        let mut inner = self.inner.lock();
+        inner.count -= 1;
+        if inner.count < 0 {
+            inner.wait_queue.push_back(current_task_id());
+            drop(inner);
+            // the resource is ours once we are woken up
+            block_current_and_run_next();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Stop task `id` from waiting, it is being killed. It gives back the
+    /// resource `down` counted for it.
+    pub fn cancel_wait(&self, id: usize) {
+        let mut inner = self.inner.lock();
+        let waiting = inner.wait_queue.len();
+        inner.wait_queue.retain(|&waiter| waiter != id);
+        if inner.wait_queue.len() < waiting {
+            inner.count += 1;
+        }
+    }
+
+    /// whether `down` would block right now
+    pub fn would_block(&self) -> bool {
+        self.inner.lock().count <= 0
+    }
+}
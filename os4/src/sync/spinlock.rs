@@ -0,0 +1,95 @@
+//! Spinlocks for data shared between harts
+
+use super::{pop_off, push_off};
+use crate::task::hart_id;
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::ops::{Deref, DerefMut};
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// no hart holds the lock
+const NO_OWNER: usize = usize::MAX;
+
+/// A lock that busy-waits until the owner lets go. Interrupts are disabled
+/// on the owner's hart while it holds the lock.
+///
+/// Never hold one across `__switch`: the task may come back on another hart,
+/// or not at all, and everybody else spins meanwhile.
+pub struct SpinLock<T> {
+    locked: AtomicBool,
+    /// the hart holding the lock, to catch it taking the lock again
+    owner: AtomicUsize,
+    /// where the owner took the lock, only touched by the owner
+    locked_at: UnsafeCell<Option<&'static Location<'static>>>,
+    inner: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+unsafe impl<T: Send> Send for SpinLock<T> {}
+
+impl<T> SpinLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            owner: AtomicUsize::new(NO_OWNER),
+            locked_at: UnsafeCell::new(None),
+            inner: UnsafeCell::new(value),
+        }
+    }
+    /// Spin until we own the lock. Panics if this hart holds it already,
+    /// naming where it took it, rather than spinning forever.
+    #[track_caller]
+    pub fn lock(&self) -> SpinLockGuard<'_, T> {
+        push_off();
+        let caller = Location::caller();
+        let hart = hart_id();
+        if self.owner.load(Ordering::Relaxed) == hart {
+            match unsafe { *self.locked_at.get() } {
+                Some(first) => panic!("locked at {} while already locked at {}", caller, first),
+                None => panic!("locked at {} while already locked", caller),
+            }
+        }
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            while self.locked.load(Ordering::Relaxed) {
+                spin_loop();
+            }
+        }
+        self.owner.store(hart, Ordering::Relaxed);
+        unsafe {
+            *self.locked_at.get() = Some(caller);
+        }
+        SpinLockGuard { lock: self }
+    }
+}
+
+/// access to the data of a [`SpinLock`], which is released on drop along
+/// with the interrupts
+pub struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.inner.get() }
+    }
+}
+
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.inner.get() }
+    }
+}
+
+impl<T> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.owner.store(NO_OWNER, Ordering::Relaxed);
+        self.lock.locked.store(false, Ordering::Release);
+        pop_off();
+    }
+}
@@ -1,31 +1,85 @@
-//! Uniprocessor interior mutability primitives
+//! Interior mutability for data only one hart touches
 
-use core::cell::{RefCell, RefMut};
+use super::{pop_off, push_off};
+use core::cell::{Cell, RefCell, RefMut};
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use core::panic::Location;
 
 /// Wrap a static data structure inside it so that we are
 /// able to access it without any `unsafe`.
 ///
-/// We should only use it in uniprocessor.
+/// We should only use it for per-hart state, anything shared between harts
+/// goes in a [`SpinLock`](super::SpinLock).
 ///
 /// In order to get mutable reference of inner data, call
-/// `exclusive_access`.
+/// `exclusive_access`. Interrupts stay disabled while the guard lives, so
+/// a handler cannot find the data borrowed.
 pub struct UPSafeCell<T> {
     /// inner data
     inner: RefCell<T>,
+    /// where the live borrow was taken, reported if another one is tried
+    borrowed_at: Cell<Option<&'static Location<'static>>>,
 }
 
 unsafe impl<T> Sync for UPSafeCell<T> {}
 
 impl<T> UPSafeCell<T> {
-    /// User is responsible to guarantee that inner struct is only used in
-    /// uniprocessor.
+    /// User is responsible to guarantee that inner struct is only used by
+    /// one hart.
     pub unsafe fn new(value: T) -> Self {
         Self {
             inner: RefCell::new(value),
+            borrowed_at: Cell::new(None),
         }
     }
-    /// Panic if the data has been borrowed.
-    pub fn exclusive_access(&self) -> RefMut<'_, T> {
-        self.inner.borrow_mut()
+    /// Panic if the data has been borrowed, naming both borrow sites.
+    #[track_caller]
+    pub fn exclusive_access(&self) -> UPSafeCellGuard<'_, T> {
+        push_off();
+        let caller = Location::caller();
+        let inner = match self.inner.try_borrow_mut() {
+            Ok(inner) => inner,
+            Err(_) => match self.borrowed_at.get() {
+                Some(first) => panic!("borrowed at {} while already borrowed at {}", caller, first),
+                None => panic!("borrowed at {} while already borrowed", caller),
+            },
+        };
+        self.borrowed_at.set(Some(caller));
+        UPSafeCellGuard {
+            inner: ManuallyDrop::new(inner),
+            cell: self,
+        }
+    }
+}
+
+/// a borrow of the data of a [`UPSafeCell`], interrupts are disabled until
+/// it is dropped
+pub struct UPSafeCellGuard<'a, T> {
+    inner: ManuallyDrop<RefMut<'a, T>>,
+    cell: &'a UPSafeCell<T>,
+}
+
+impl<T> Deref for UPSafeCellGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for UPSafeCellGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<T> Drop for UPSafeCellGuard<'_, T> {
+    fn drop(&mut self) {
+        self.cell.borrowed_at.set(None);
+        // the borrow has to end before an interrupt may come in
+        unsafe {
+            ManuallyDrop::drop(&mut self.inner);
+        }
+        pop_off();
     }
 }
@@ -1,21 +1,66 @@
 //! File and filesystem-related syscalls
+//!
+//! All of them return -1 for a file descriptor that is not open.
 
-use crate::mm::translated_byte_buffer;
-use crate::task::current_user_token;
-
-const FD_STDOUT: usize = 1;
+use crate::fs::make_pipe;
+use crate::mm::{translated_byte_buffer, translated_refmut, UserBuffer};
+use crate::task::{current_user_token, with_current_process};
 
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    match fd {
-        FD_STDOUT => {
-            let buffers = translated_byte_buffer(current_user_token(), buf, len);
-            for buffer in buffers {
-                print!("{}", core::str::from_utf8(buffer).unwrap());
-            }
-            len as isize
-        }
-        _ => {
-            panic!("Unsupported fd in sys_write!");
-        }
+    let file = match with_current_process(|process| process.get_file(fd)) {
+        Some(file) if file.writable() => file,
+        _ => return -1,
+    };
+    match translated_byte_buffer(current_user_token(), buf, len) {
+        Some(buffers) => file.write(UserBuffer::new(buffers)) as isize,
+        None => -1,
+    }
+}
+
+pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let file = match with_current_process(|process| process.get_file(fd)) {
+        Some(file) if file.readable() => file,
+        _ => return -1,
+    };
+    match translated_byte_buffer(current_user_token(), buf, len) {
+        Some(buffers) => file.read(UserBuffer::new(buffers)) as isize,
+        None => -1,
     }
 }
+
+pub fn sys_close(fd: usize) -> isize {
+    let closed =
+        with_current_process(|process| process.fd_table.get_mut(fd).and_then(|file| file.take()));
+    match closed {
+        Some(_) => 0,
+        None => -1,
+    }
+}
+
+/// Open another file descriptor for the file behind `fd`, the lowest free
+/// one, and return it.
+pub fn sys_dup(fd: usize) -> isize {
+    with_current_process(|process| match process.get_file(fd) {
+        Some(file) => process.alloc_fd(file) as isize,
+        None => -1,
+    })
+}
+
+/// Create a pipe and store the file descriptors of its read end and write
+/// end in `pipe[0]` and `pipe[1]`.
+pub fn sys_pipe(pipe: *mut usize) -> isize {
+    let token = current_user_token();
+    let (read_fd_ptr, write_fd_ptr) = match (
+        translated_refmut(token, pipe),
+        translated_refmut(token, pipe.wrapping_add(1)),
+    ) {
+        (Some(read_fd_ptr), Some(write_fd_ptr)) => (read_fd_ptr, write_fd_ptr),
+        _ => return -1,
+    };
+    let (read_end, write_end) = make_pipe();
+    let (read_fd, write_fd) =
+        with_current_process(|process| (process.alloc_fd(read_end), process.alloc_fd(write_end)));
+    *read_fd_ptr = read_fd;
+    *write_fd_ptr = write_fd;
+    0
+}
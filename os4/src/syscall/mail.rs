@@ -0,0 +1,49 @@
+//! Mailbox syscalls: short messages between processes
+//!
+//! A message is at most `MAX_MAIL_LEN` bytes, longer ones are cut. Neither
+//! call blocks: they return -1 if the mailbox is full or empty.
+
+use crate::config::MAX_MAIL_LEN;
+use crate::mm::{translated_byte_buffer, UserBuffer};
+use crate::task::{current_user_token, with_current_process, with_process};
+
+/// Take the oldest message from our mailbox into `buf`, cut to `len` bytes.
+/// Returns the number of bytes copied. With `len == 0` only checks whether
+/// there is a message.
+pub fn sys_mail_read(buf: *mut u8, len: usize) -> isize {
+    if len == 0 {
+        let empty = with_current_process(|process| process.mailbox.is_empty());
+        return if empty { -1 } else { 0 };
+    }
+    // a bad `buf` must not cost us the message
+    let mut buffer = match translated_byte_buffer(current_user_token(), buf, len) {
+        Some(buffers) => UserBuffer::new(buffers),
+        None => return -1,
+    };
+    match with_current_process(|process| process.mailbox.pop()) {
+        Some(message) => buffer.fill_from(&message) as isize,
+        None => -1,
+    }
+}
+
+/// Send the `len` bytes at `buf` to the mailbox of process `pid`, returns
+/// the number of bytes sent. With `len == 0` only checks whether there is
+/// room.
+pub fn sys_mail_write(pid: usize, buf: *const u8, len: usize) -> isize {
+    let len = len.min(MAX_MAIL_LEN);
+    let message = match translated_byte_buffer(current_user_token(), buf, len) {
+        Some(buffers) => UserBuffer::new(buffers).to_vec(),
+        None => return -1,
+    };
+    let sent = with_process(pid, |process| {
+        if len == 0 {
+            !process.mailbox.is_full()
+        } else {
+            process.mailbox.push(message)
+        }
+    });
+    match sent {
+        Some(true) => len as isize,
+        _ => -1,
+    }
+}
@@ -1,8 +1,12 @@
 
-use crate::mm::page_table;
+use crate::mm::{copy_to_user, translated_str};
 use crate::config::{MAX_SYSCALL_NUM, PAGE_SIZE};
+use crate::loader::get_app_data_by_name;
 use crate::task::{exit_current_and_run_next, suspend_current_and_run_next, TaskStatus, get_task_info, current_user_token, mmap, TASK_MANAGER, unmap};
-use crate::timer::get_time_us;
+use crate::task::{current_pid, exec_current, fork_current, set_current_priority, spawn, waitpid};
+use crate::task::{block_current_and_run_next, current_task_id, current_task_stats};
+use crate::task::set_current_cpu_limit;
+use crate::timer::{add_timer, get_time_ms, get_time_us};
 
 #[repr(C)]
 #[derive(Debug)]
@@ -11,6 +15,17 @@ pub struct TimeVal {
     pub usec: usize,
 }
 
+bitflags! {
+    /// `flags` of `sys_mmap`, with the same values as on Linux
+    pub struct MmapFlags: usize {
+        const MAP_SHARED = 0x01;
+        const MAP_PRIVATE = 0x02;
+        const MAP_FIXED = 0x10;
+        const MAP_ANONYMOUS = 0x20;
+        const MAP_POPULATE = 0x8000;
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct TaskInfo {
     pub status: TaskStatus,
@@ -18,12 +33,76 @@ pub struct TaskInfo {
     pub time: usize,
 }
 
+/// CPU time accounting of a task, all times in microseconds
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct TaskStats {
+    pub user_time: usize,
+    pub kernel_time: usize,
+    /// time since the task was first scheduled
+    pub wall_time: usize,
+    pub voluntary_switches: usize,
+    pub involuntary_switches: usize,
+    /// when the task was first and last switched in, since boot
+    pub first_run_time: usize,
+    pub last_run_time: usize,
+}
+
 pub fn sys_exit(exit_code: i32) -> ! {
     info!("[kernel] Application exited with code {}", exit_code);
-    exit_current_and_run_next();
+    exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
 
+pub fn sys_getpid() -> isize {
+    current_pid() as isize
+}
+
+/// the child gets a copy of the address space and returns 0. Only a process
+/// down to its main thread may fork
+pub fn sys_fork() -> isize {
+    fork_current()
+}
+
+/// Replace the current program with the app named by the string at `path`.
+/// Like `fork`, only allowed once the main thread is the only one left.
+pub fn sys_exec(path: *const u8) -> isize {
+    let path = match translated_str(current_user_token(), path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    match get_app_data_by_name(path.as_str()) {
+        Some(data) => exec_current(data),
+        None => -1,
+    }
+}
+
+/// Start the app named by the string at `path` as a new child, without
+/// copying our address space. Returns the pid of the child or -1.
+pub fn sys_spawn(path: *const u8) -> isize {
+    let path = match translated_str(current_user_token(), path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    match get_app_data_by_name(path.as_str()) {
+        Some(data) => spawn(data),
+        None => -1,
+    }
+}
+
+/// Return -1 if there is no such child, -2 if it is still running, or the
+/// pid of the reaped child.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+    waitpid(pid, exit_code_ptr)
+}
+
+/// Block the current task for `sleep_ms` milliseconds without using the CPU.
+pub fn sys_sleep(sleep_ms: usize) -> isize {
+    add_timer(get_time_ms().saturating_add(sleep_ms), current_task_id());
+    block_current_and_run_next();
+    0
+}
+
 /// current task gives up resources for other tasks
 pub fn sys_yield() -> isize {
     suspend_current_and_run_next();
@@ -33,15 +112,14 @@ pub fn sys_yield() -> isize {
 // TODO YOUR JOB: 引入虚地址后重写 sys_get_time
 pub fn sys_get_time(_ts: *mut TimeVal, _tz: usize) -> isize {
     let _us = get_time_us();
-    let ts = page_table::get_phy_addr(current_user_token(), _ts as usize) as *mut TimeVal;
-
-    unsafe {
-        *ts = TimeVal {
-            sec: _us / 1_000_000,
-            usec: _us % 1_000_000,
-        };
+    let time = TimeVal {
+        sec: _us / 1_000_000,
+        usec: _us % 1_000_000,
+    };
+    match copy_to_user(current_user_token(), _ts, &time) {
+        Some(()) => 0,
+        None => -1,
     }
-    0
     // 能否直接尝试通过，尝试不通过，原因主要在于传入的是VPN+offset需要转化成为PPN+offset
     // unsafe {
     //     *_ts = TimeVal {
@@ -52,18 +130,52 @@ pub fn sys_get_time(_ts: *mut TimeVal, _tz: usize) -> isize {
     // 0
 }
 
-// CLUE: 从 ch4 开始不再对调度算法进行测试~
-pub fn sys_set_priority(_prio: isize) -> isize {
-    -1
+/// Set the stride-scheduling priority of the current task. It must be at
+/// least 2, and is returned on success.
+pub fn sys_set_priority(prio: isize) -> isize {
+    if prio < 2 {
+        return -1;
+    }
+    set_current_priority(prio as usize);
+    prio
+}
+
+/// Let the current task use `limit_ms` milliseconds of CPU time in all, 0
+/// for no limit. A task over its limit is terminated at the next timer tick.
+/// Returns the old limit, 0 if there was none, or -1 if `limit_ms` is
+/// negative or too large to count in microseconds.
+pub fn sys_set_cpu_limit(limit_ms: isize) -> isize {
+    if limit_ms < 0 {
+        return -1;
+    }
+    let limit_us = match (limit_ms as usize).checked_mul(1000) {
+        Some(0) => None,
+        Some(us) => Some(us),
+        None => return -1,
+    };
+    set_current_cpu_limit(limit_us).map_or(0, |us| (us / 1000) as isize)
 }
 
 // YOUR JOB:   扩展内核以实现 sys_mmap 和 sys_munmap
-#[allow(unused_variables)]
-pub fn sys_mmap(start: usize, len: usize, port: usize) -> isize {
+/// Map anonymous memory.
+///
+/// `flags == 0` is the original 3-argument call: a nonzero `start` must be
+/// free and 0 is returned on success, `start == 0` lets the kernel choose and
+/// returns the address. `len == 0` maps nothing and returns 0. Otherwise `flags` is a Linux-style [`MmapFlags`]:
+/// without `MAP_FIXED` the `start` is only a hint, and the mapped address is
+/// returned. Mappings are always populated eagerly and there are no files, so
+/// `MAP_POPULATE` is implied and `MAP_ANONYMOUS` is required.
+pub fn sys_mmap(
+    start: usize,
+    len: usize,
+    port: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
     // TODO [start, start+len)中存在已经被映射的页
     // TODO 物理内存不足
 
-    let mut align_len = len;
     if start % PAGE_SIZE != 0 {
         return -1;
     }
@@ -74,18 +186,51 @@ pub fn sys_mmap(start: usize, len: usize, port: usize) -> isize {
     // } 
     // len shouldn't biggest than the maximum size of stack allocater
     // we finish this part in TASK_MANAGER
-    if len % PAGE_SIZE != 0 {
-        align_len = (len/PAGE_SIZE + 1) * PAGE_SIZE;
-    }
+    let align_len = match len.checked_add(PAGE_SIZE - 1) {
+        Some(len) => len / PAGE_SIZE * PAGE_SIZE,
+        None => return -1,
+    };
     // port: the other part of port should be 0; the port shouldn't be 0
     if port & !0x07 != 0 || port & 0x7 == 0 {
-        println!("[syscall::process::sysmmap]port illeglity!");
+        debug!("[kernel] mmap: bad prot {:#x}", port);
+        return -1;
+    }
+
+    if flags == 0 {
+        // an empty mapping has always succeeded without mapping anything
+        if len == 0 {
+            return 0;
+        }
+        let ret = mmap(start, align_len, port, start != 0, false);
+        return if start != 0 && ret >= 0 { 0 } else { ret };
+    }
+    let flags = match MmapFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return -1,
+    };
+    // as on Linux, where it is `EINVAL`
+    if len == 0 {
+        return -1;
+    }
+    // exactly one of shared and private. Shared pages stay shared with the
+    // children we fork, private ones are copied
+    if flags.contains(MmapFlags::MAP_SHARED) == flags.contains(MmapFlags::MAP_PRIVATE) {
+        return -1;
+    }
+    // no file-backed mappings, and the fd of an anonymous one must be -1
+    if !flags.contains(MmapFlags::MAP_ANONYMOUS) || fd as isize != -1 || offset != 0 {
         return -1;
     }
 
     // alloacte 
 
-    mmap(start, align_len, port)
+    mmap(
+        start,
+        align_len,
+        port,
+        flags.contains(MmapFlags::MAP_FIXED),
+        flags.contains(MmapFlags::MAP_SHARED),
+    )
 }
 
 pub fn sys_munmap(_start: usize, _len: usize) -> isize {
@@ -104,18 +249,33 @@ pub fn sys_munmap(_start: usize, _len: usize) -> isize {
 // YOUR JOB: 引入虚地址后重写 sys_task_info
 pub fn sys_task_info(ti: *mut TaskInfo) -> isize {
     let (s, st, t) = get_task_info();
-    let ts = page_table::get_phy_addr(current_user_token(), ti as usize) as *mut TaskInfo;
-
-    // 1. trying to find the location of the raw pointer.
-    // 2. if error which this area havn't been alloc then error
-    //      else just give the answer inside it.
+    let info = TaskInfo {
+        status: s,
+        syscall_times: st,
+        time: t / 1_000,
+    };
+    // the info is large enough to cross a page of the user stack
+    match copy_to_user(current_user_token(), ti, &info) {
+        Some(()) => 0,
+        None => -1,
+    }
+}
 
-    unsafe {
-        *ts = TaskInfo{
-            status: s,
-            syscall_times: st,
-            time:t / 1_000,
-        }
+/// Fill `ts` with the CPU time accounting of the current task.
+pub fn sys_task_stats(ts: *mut TaskStats) -> isize {
+    let stats = current_task_stats();
+    let now = get_time_us();
+    let task_stats = TaskStats {
+        user_time: stats.user_time,
+        kernel_time: stats.kernel_time,
+        wall_time: stats.wall_time(now),
+        voluntary_switches: stats.voluntary_switches,
+        involuntary_switches: stats.involuntary_switches,
+        first_run_time: stats.first_run_time.unwrap_or(0),
+        last_run_time: stats.last_run_time,
+    };
+    match copy_to_user(current_user_token(), ts, &task_stats) {
+        Some(()) => 0,
+        None => -1,
     }
-    0
 }
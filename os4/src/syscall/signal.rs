@@ -0,0 +1,64 @@
+//! Signals: sending, catching and blocking them, and returning from handlers
+
+use crate::mm::translated_refmut;
+use crate::task::{current_user_token, kill, sigaction, sigprocmask, sigreturn};
+use crate::task::{SignalAction, SignalFlags};
+
+/// Send signal `signum` to the process `pid`. Returns -1 if there is no such
+/// signal or live process.
+pub fn sys_kill(pid: usize, signum: usize) -> isize {
+    kill(pid, signum)
+}
+
+/// Set the action of signal `signum` to `*action` unless it is null, and
+/// store the old one in `*old_action` unless that is null. Returns -1 for
+/// `SIGKILL`, `SIGSTOP` and signals that do not exist.
+pub fn sys_sigaction(
+    signum: usize,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> isize {
+    let token = current_user_token();
+    let action = if action.is_null() {
+        None
+    } else {
+        let action = match translated_refmut(token, action as *mut SignalAction) {
+            Some(action) => *action,
+            None => return -1,
+        };
+        Some(SignalAction {
+            handler: action.handler,
+            mask: SignalFlags::from_bits_truncate(action.mask.bits()),
+        })
+    };
+    let old_action = if old_action.is_null() {
+        None
+    } else {
+        match translated_refmut(token, old_action) {
+            Some(old_action) => Some(old_action),
+            None => return -1,
+        }
+    };
+    match sigaction(signum, action) {
+        Some(old) => {
+            if let Some(old_action) = old_action {
+                *old_action = old;
+            }
+            0
+        }
+        None => -1,
+    }
+}
+
+/// Block the signals in `mask` and no others, returning the old mask.
+pub fn sys_sigprocmask(mask: u32) -> isize {
+    match SignalFlags::from_bits(mask) {
+        Some(mask) => sigprocmask(mask).bits() as isize,
+        None => -1,
+    }
+}
+
+/// Called by the trampoline signal handlers return to.
+pub fn sys_sigreturn() -> isize {
+    sigreturn()
+}
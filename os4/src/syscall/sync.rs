@@ -0,0 +1,150 @@
+//! Mutexes, semaphores and condition variables shared by the threads of a
+//! process. All calls on an id that was never created return -1.
+//!
+//! Taking and releasing goes through the process's deadlock detector. Once
+//! enabled, a lock or down that would block returns [`DEADLOCK`] instead if
+//! no order is left in which the threads could all finish.
+
+use crate::sync::{Condvar, Mutex, MutexBlocking, MutexSpin, Resource, Semaphore};
+use crate::task::{current_tid, with_current_process};
+use alloc::sync::Arc;
+
+/// returned instead of blocking into a deadlock
+const DEADLOCK: isize = -0xDEAD;
+
+/// Create a mutex, blocking or spinning, and return its id.
+pub fn sys_mutex_create(blocking: bool) -> isize {
+    let mutex: Arc<dyn Mutex> = if blocking {
+        Arc::new(MutexBlocking::new())
+    } else {
+        Arc::new(MutexSpin::new())
+    };
+    with_current_process(|process| process.add_mutex(mutex)) as isize
+}
+
+pub fn sys_mutex_lock(mutex_id: usize) -> isize {
+    match with_current_process(|process| process.get_mutex(mutex_id)) {
+        Some(mutex) => lock_mutex(mutex_id, mutex.as_ref()),
+        None => -1,
+    }
+}
+
+pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
+    match with_current_process(|process| process.get_mutex(mutex_id)) {
+        Some(mutex) => unlock_mutex(mutex_id, mutex.as_ref()),
+        None => -1,
+    }
+}
+
+/// Take `mutex`, or return [`DEADLOCK`] if waiting for it would deadlock.
+fn lock_mutex(mutex_id: usize, mutex: &dyn Mutex) -> isize {
+    let tid = current_tid();
+    let resource = Resource::Mutex(mutex_id);
+    if mutex.is_locked() && !with_current_process(|process| process.deadlock.request(tid, resource))
+    {
+        return DEADLOCK;
+    }
+    if !mutex.lock() {
+        with_current_process(|process| process.deadlock.acquire(tid, resource));
+    }
+    0
+}
+
+/// Release `mutex`, or return -1 if the current task does not hold it.
+fn unlock_mutex(mutex_id: usize, mutex: &dyn Mutex) -> isize {
+    let tid = current_tid();
+    let handed_to = match mutex.unlock() {
+        Ok(handed_to) => handed_to,
+        Err(_) => return -1,
+    };
+    with_current_process(|process| {
+        let waiter = handed_to.and_then(|id| process.tid_of(id));
+        process
+            .deadlock
+            .release(tid, Resource::Mutex(mutex_id), waiter);
+    });
+    0
+}
+
+/// Create a semaphore with `res_count` resources and return its id.
+pub fn sys_semaphore_create(res_count: usize) -> isize {
+    let semaphore = Arc::new(Semaphore::new(res_count));
+    with_current_process(|process| process.add_semaphore(semaphore, res_count)) as isize
+}
+
+pub fn sys_semaphore_up(sem_id: usize) -> isize {
+    let semaphore = match with_current_process(|process| process.get_semaphore(sem_id)) {
+        Some(semaphore) => semaphore,
+        None => return -1,
+    };
+    let tid = current_tid();
+    let handed_to = semaphore.up();
+    with_current_process(|process| {
+        let waiter = handed_to.and_then(|id| process.tid_of(id));
+        process
+            .deadlock
+            .release(tid, Resource::Semaphore(sem_id), waiter);
+    });
+    0
+}
+
+/// Take a resource of the semaphore, or return [`DEADLOCK`] if waiting for
+/// one would deadlock.
+pub fn sys_semaphore_down(sem_id: usize) -> isize {
+    let semaphore = match with_current_process(|process| process.get_semaphore(sem_id)) {
+        Some(semaphore) => semaphore,
+        None => return -1,
+    };
+    let tid = current_tid();
+    let resource = Resource::Semaphore(sem_id);
+    if semaphore.would_block()
+        && !with_current_process(|process| process.deadlock.request(tid, resource))
+    {
+        return DEADLOCK;
+    }
+    if !semaphore.down() {
+        with_current_process(|process| process.deadlock.acquire(tid, resource));
+    }
+    0
+}
+
+/// Turn deadlock detection for the current process on (1) or off (0).
+pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
+    if enabled > 1 {
+        return -1;
+    }
+    with_current_process(|process| process.deadlock.enabled = enabled == 1);
+    0
+}
+
+/// Create a condition variable and return its id.
+pub fn sys_condvar_create() -> isize {
+    let condvar = Arc::new(Condvar::new());
+    with_current_process(|process| process.add_condvar(condvar)) as isize
+}
+
+pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+    match with_current_process(|process| process.get_condvar(condvar_id)) {
+        Some(condvar) => {
+            condvar.signal();
+            0
+        }
+        None => -1,
+    }
+}
+
+/// Release the mutex `mutex_id`, wait for a signal and take it again.
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    let (condvar, mutex) = with_current_process(|process| {
+        (process.get_condvar(condvar_id), process.get_mutex(mutex_id))
+    });
+    match (condvar, mutex) {
+        (Some(condvar), Some(mutex)) => {
+            if !condvar.wait(|| unlock_mutex(mutex_id, mutex.as_ref()) == 0) {
+                return -1;
+            }
+            lock_mutex(mutex_id, mutex.as_ref())
+        }
+        _ => -1,
+    }
+}
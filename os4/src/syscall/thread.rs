@@ -0,0 +1,17 @@
+use crate::task::{current_tid, thread_create, waittid};
+
+/// Start a thread of the current process at `entry`, with `arg` in `a0`.
+/// Returns its tid, or -1 if there is no room for its stack.
+pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+    thread_create(entry, arg)
+}
+
+pub fn sys_gettid() -> isize {
+    current_tid() as isize
+}
+
+/// Return -1 if there is no such thread or it is the caller, -2 if it is
+/// still running, or the exit code of the reaped thread.
+pub fn sys_waittid(tid: usize) -> isize {
+    waittid(tid)
+}
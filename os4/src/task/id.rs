@@ -0,0 +1,79 @@
+//! Process, task and thread identifiers
+//!
+//! Pids name processes and task ids name threads kernel-wide, each from
+//! their own allocator. Both are owned by handles which give the id back on
+//! drop, so ids of reaped processes and threads are reused. Thread ids
+//! inside one process come from a plain [`RecycleAllocator`].
+
+use crate::sync::SpinLock;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// id allocator, freed ids are handed out first
+pub struct RecycleAllocator {
+    current: usize,
+    recycled: Vec<usize>,
+}
+
+impl RecycleAllocator {
+    /// ids below `first` are never handed out
+    pub fn new(first: usize) -> Self {
+        RecycleAllocator {
+            current: first,
+            recycled: Vec::new(),
+        }
+    }
+    pub fn alloc(&mut self) -> usize {
+        if let Some(id) = self.recycled.pop() {
+            id
+        } else {
+            self.current += 1;
+            self.current - 1
+        }
+    }
+    pub fn dealloc(&mut self, id: usize) {
+        assert!(id < self.current);
+        assert!(
+            !self.recycled.iter().any(|i| *i == id),
+            "id {} has been deallocated!",
+            id
+        );
+        self.recycled.push(id);
+    }
+}
+
+lazy_static! {
+    /// pid 0 is never handed out: `fork` returns 0 to the child
+    static ref PID_ALLOCATOR: SpinLock<RecycleAllocator> =
+        SpinLock::new(RecycleAllocator::new(1));
+    static ref TASK_ID_ALLOCATOR: SpinLock<RecycleAllocator> =
+        SpinLock::new(RecycleAllocator::new(0));
+}
+
+/// a pid, returned to the allocator on drop
+pub struct PidHandle(pub usize);
+
+impl Drop for PidHandle {
+    fn drop(&mut self) {
+        PID_ALLOCATOR.lock().dealloc(self.0);
+    }
+}
+
+/// allocate a new pid
+pub fn pid_alloc() -> PidHandle {
+    PidHandle(PID_ALLOCATOR.lock().alloc())
+}
+
+/// a kernel-wide task id, returned to the allocator on drop
+pub struct TaskIdHandle(pub usize);
+
+impl Drop for TaskIdHandle {
+    fn drop(&mut self) {
+        TASK_ID_ALLOCATOR.lock().dealloc(self.0);
+    }
+}
+
+/// allocate a new task id
+pub fn task_id_alloc() -> TaskIdHandle {
+    TaskIdHandle(TASK_ID_ALLOCATOR.lock().alloc())
+}
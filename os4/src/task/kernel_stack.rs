@@ -0,0 +1,64 @@
+//! Kernel stacks of tasks
+//!
+//! Each task gets a kernel stack in one of the slots laid out by
+//! [`kernel_stack_position`]. The stack is unmapped from [`KERNEL_SPACE`] when
+//! its [`KernelStack`] is dropped, and the slot goes back to the allocator so
+//! the next task can reuse the same virtual range.
+
+use super::hart_id;
+use super::id::RecycleAllocator;
+use crate::board::hart_mask;
+use crate::config::{kernel_stack_position, KERNEL_STACK_SIZE};
+use crate::mm::{MapPermission, VirtAddr, KERNEL_SPACE};
+use crate::sbi::remote_sfence_vma;
+use crate::sync::SpinLock;
+use lazy_static::*;
+
+lazy_static! {
+    static ref KSTACK_ALLOCATOR: SpinLock<RecycleAllocator> =
+        SpinLock::new(RecycleAllocator::new(0));
+}
+
+/// Take a free slot and map a fresh kernel stack there.
+pub fn kstack_alloc() -> KernelStack {
+    let slot = KSTACK_ALLOCATOR.lock().alloc();
+    let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(slot);
+    KERNEL_SPACE.lock().insert_framed_area(
+        kernel_stack_bottom.into(),
+        kernel_stack_top.into(),
+        MapPermission::R | MapPermission::W,
+    );
+    KernelStack { slot }
+}
+
+/// a kernel stack mapped in kernel space, unmapped on drop
+pub struct KernelStack {
+    slot: usize,
+}
+
+impl KernelStack {
+    pub fn get_top(&self) -> usize {
+        let (_, kernel_stack_top) = kernel_stack_position(self.slot);
+        kernel_stack_top
+    }
+}
+
+impl Drop for KernelStack {
+    fn drop(&mut self) {
+        let (kernel_stack_bottom, _) = kernel_stack_position(self.slot);
+        let kernel_stack_bottom_va: VirtAddr = kernel_stack_bottom.into();
+        KERNEL_SPACE
+            .lock()
+            .remove_area_with_start_vpn(kernel_stack_bottom_va.into());
+        // the slot may be mapped to other frames soon, drop stale translations
+        // here and on the other harts, which share the kernel space
+        unsafe {
+            core::arch::asm!("sfence.vma");
+        }
+        let others = hart_mask() & !(1 << hart_id());
+        if others != 0 {
+            remote_sfence_vma(others, kernel_stack_bottom, KERNEL_STACK_SIZE);
+        }
+        KSTACK_ALLOCATOR.lock().dealloc(self.slot);
+    }
+}
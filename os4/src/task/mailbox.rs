@@ -0,0 +1,36 @@
+//! Mailboxes: bounded queues of short messages sent to a process
+//!
+//! Neither sending nor receiving blocks, a full or empty mailbox is reported
+//! to the caller instead.
+
+use crate::config::{MAX_MAIL_LEN, MAX_MAIL_NUM};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+#[derive(Default)]
+pub struct Mailbox {
+    /// oldest first
+    messages: VecDeque<Vec<u8>>,
+}
+
+impl Mailbox {
+    pub fn is_full(&self) -> bool {
+        self.messages.len() >= MAX_MAIL_NUM
+    }
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+    /// Queue `message`, returns false if the mailbox is full.
+    pub fn push(&mut self, message: Vec<u8>) -> bool {
+        assert!(message.len() <= MAX_MAIL_LEN);
+        if self.is_full() {
+            return false;
+        }
+        self.messages.push_back(message);
+        true
+    }
+    /// Take the oldest message.
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        self.messages.pop_front()
+    }
+}
@@ -4,27 +4,48 @@
 //! implemented here.
 //!
 //! A single global instance of [`TaskManager`] called `TASK_MANAGER` controls
-//! all the tasks in the operating system.
+//! all the tasks in the operating system. All harts share it, each runs its
+//! own idle loop, [`run_tasks()`], which takes the next `Ready` task from it.
 //!
 //! Be careful when you see [`__switch`]. Control flow around this function
 //! might not be what you expect.
 
 mod context;
+mod kernel_stack;
+mod mailbox;
+mod id;
+mod process;
+mod processor;
+mod scheduler;
+mod signal;
 mod switch;
+mod table;
 #[allow(clippy::module_inception)]
 mod task;
 
-use crate::config::MAX_SYSCALL_NUM;
+use crate::config::{EXIT_LOG_LEN, MAX_SYSCALL_NUM, PAGE_SIZE, SIGRETURN_TRAMPOLINE};
 use crate::loader::{get_app_data, get_num_app};
-use crate::mm::{VirtAddr, MapPermission, VirtPageNum};
-use crate::sync::UPSafeCell;
-use crate::trap::TrapContext;
+use crate::mm::{translated_refmut, MapPermission, MemorySet, VirtAddr, VirtPageNum};
+use crate::sbi::{remote_sfence_vma, shutdown};
+use crate::sync::SpinLock;
+use crate::trap::{core_dump, TrapContext};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
 use alloc::vec::Vec;
 use lazy_static::*;
 pub use switch::__switch;
-pub use task::{TaskControlBlock, TaskStatus};
+pub use task::{ExitReason, TaskControlBlock, TaskStatsInfo, TaskStatus};
 
 pub use context::TaskContext;
+pub use kernel_stack::{kstack_alloc, KernelStack};
+pub use id::{pid_alloc, task_id_alloc, PidHandle, TaskIdHandle};
+pub use process::{trap_cx_bottom_from_tid, ProcessControlBlock};
+pub use processor::{current_task_id, hart_id};
+use processor::{idle_task_cx_ptr, kick_idle_hart, set_current, set_idle, take_current};
+pub use scheduler::Scheduler;
+pub use signal::{SignalAction, SignalFlags, MAX_SIG, SIG_DFL, SIG_IGN};
+use table::TaskTable;
 
 /// The task manager, where all the tasks are managed.
 ///
@@ -32,22 +53,266 @@ pub use context::TaskContext;
 /// and task context switching. For convenience, you can find wrappers around it
 /// in the module level.
 ///
-/// Most of `TaskManager` are hidden behind the field `inner`, to defer
-/// borrowing checks to runtime. You can see examples on how to use `inner` in
+/// Most of `TaskManager` are hidden behind the field `inner`, a spinlock all
+/// harts take turns at. You can see examples on how to use `inner` in
 /// existing functions on `TaskManager`.
 pub struct TaskManager {
-    /// total number of tasks
-    num_app: usize,
     /// use inner value to get mutable access
-    inner: UPSafeCell<TaskManagerInner>,
+    inner: SpinLock<TaskManagerInner>,
 }
 
-/// The task manager inner in 'UPSafeCell'
+/// The task manager inner in 'SpinLock'
 struct TaskManagerInner {
-    /// task list
-    tasks: Vec<TaskControlBlock>,
-    /// id of current `Running` task
-    current_task: usize,
+    /// all threads, grows on `fork` and `thread_create` and shrinks when
+    /// zombies are reaped
+    tasks: TaskTable,
+    /// all processes by pid
+    processes: BTreeMap<usize, ProcessControlBlock>,
+    /// decides which `Ready` task runs next
+    scheduler: Box<dyn Scheduler>,
+    /// the processes that have exited, for the summary at shutdown
+    exit_log: ExitLog,
+}
+
+/// The latest [`EXIT_LOG_LEN`] exits, and counts of the ones before, so
+/// that a long run does not fill the heap with them.
+#[derive(Default)]
+struct ExitLog {
+    records: VecDeque<ExitRecord>,
+    /// exits whose record was dropped to make room
+    dropped: usize,
+    /// exits with [`ExitReason::Fault`], dropped or not
+    faults: usize,
+}
+
+impl ExitLog {
+    fn push(&mut self, record: ExitRecord) {
+        if record.reason == ExitReason::Fault {
+            self.faults += 1;
+        }
+        if self.records.len() == EXIT_LOG_LEN {
+            self.records.pop_front();
+            self.dropped += 1;
+        }
+        self.records.push_back(record);
+    }
+}
+
+/// what is left of an exited process for the summary at shutdown
+struct ExitRecord {
+    pid: usize,
+    exit_code: i32,
+    reason: ExitReason,
+    user_time: usize,
+    kernel_time: usize,
+}
+
+impl TaskManagerInner {
+    /// Make the `Blocked` task `id` `Ready` again. A task still `Running`
+    /// is on its way to block, it will not. Does nothing otherwise, e.g. it
+    /// was woken up already.
+    fn wakeup_task(&mut self, id: usize) {
+        let task = match self.tasks.get_mut(id) {
+            Some(task) => task,
+            None => return,
+        };
+        match task.task_status {
+            TaskStatus::Blocked => {
+                task.task_status = TaskStatus::Ready;
+                // if it is still switching away, the idle loop adds it
+                if task.on_cpu.is_none() {
+                    let priority = task.priority;
+                    self.scheduler.add(id, priority);
+                    kick_idle_hart();
+                }
+            }
+            TaskStatus::Running => task.wakeup_pending = true,
+            _ => {}
+        }
+    }
+
+    /// Whether task `id` has a signal pending that kills its process once
+    /// delivered: one it does not block and left to the default action.
+    fn has_fatal_signal(&self, id: usize) -> bool {
+        let task = &self.tasks[id];
+        let actions = &self.processes[&task.pid].signal_actions;
+        let mut fatal = task.signals
+            - (task.signal_mask - SignalFlags::UNCATCHABLE)
+            - SignalFlags::DEFAULT_IGNORED;
+        while let Some(signum) = fatal.first_signum() {
+            if actions[signum].handler == SIG_DFL {
+                return true;
+            }
+            fatal.remove(SignalFlags::from_signum(signum).unwrap());
+        }
+        false
+    }
+
+    /// Add a `Ready` task to the table and the scheduler, returning its id.
+    fn add_task(&mut self, task: TaskControlBlock) -> usize {
+        let priority = task.priority;
+        let id = self.tasks.insert(task);
+        self.scheduler.add(id, priority);
+        kick_idle_hart();
+        id
+    }
+
+    /// Add a process along with its main thread, returning the pid.
+    fn add_process(&mut self, process: ProcessControlBlock, thread: TaskControlBlock) -> usize {
+        let pid = process.getpid();
+        self.processes.insert(pid, process);
+        self.add_task(thread);
+        pid
+    }
+
+    fn current_process(&self) -> &ProcessControlBlock {
+        &self.processes[&self.tasks[current_task_id()].pid]
+    }
+
+    fn current_process_mut(&mut self) -> &mut ProcessControlBlock {
+        let pid = self.tasks[current_task_id()].pid;
+        self.processes.get_mut(&pid).unwrap()
+    }
+
+    /// Whether the process `pid` has threads other than the current one that
+    /// have not exited, or are still on their way off another hart.
+    fn has_other_live_threads(&self, pid: usize) -> bool {
+        let current = current_task_id();
+        self.processes[&pid].thread_ids().any(|id| {
+            let task = &self.tasks[id];
+            id != current && (task.task_status != TaskStatus::Exited || task.on_cpu.is_some())
+        })
+    }
+
+    /// Whether none of the threads of process `pid` is on a hart.
+    fn is_off_cpu(&self, pid: usize) -> bool {
+        self.processes[&pid]
+            .thread_ids()
+            .all(|id| self.tasks[id].on_cpu.is_none())
+    }
+
+    /// Have the other harts running threads of process `pid` drop what their
+    /// TLB holds of `[start, start + len)`. Ours is flushed anyway when we
+    /// switch back to user space.
+    fn flush_remote_tlb(&self, pid: usize, start: usize, len: usize) {
+        let this = hart_id();
+        let hart_mask = self.processes[&pid]
+            .thread_ids()
+            .filter_map(|id| self.tasks[id].on_cpu)
+            .filter(|hart| *hart != this)
+            .fold(0, |mask, hart| mask | 1 << hart);
+        if hart_mask != 0 {
+            remote_sfence_vma(hart_mask, start, len);
+        }
+    }
+
+    /// Pop tasks off the scheduler until one is still `Ready`.
+    fn pick_ready_task(&mut self) -> Option<usize> {
+        while let Some(id) = self.scheduler.pick_next() {
+            let ready = self
+                .tasks
+                .get(id)
+                .map_or(false, |task| task.task_status == TaskStatus::Ready);
+            if ready {
+                return Some(id);
+            }
+            warn!("[kernel] scheduler picked task {} which is not ready", id);
+        }
+        None
+    }
+
+    /// Thread `id` is done: it leaves the scheduler, the timer and the wait
+    /// queues of its process, its CPU time goes to its process and its user stack and `TrapContext` are unmapped. The TCB
+    /// stays until it is waited for or its process is reaped.
+    ///
+    /// A thread still on a hart, be it the current one or one running in user
+    /// mode elsewhere, keeps its user stack until it gets off, see
+    /// [`TaskManagerInner::release_user_res`]. The others stop at their next
+    /// trap.
+    fn exit_thread(&mut self, id: usize, exit_code: i32) {
+        let current = current_task_id();
+        // it may have been on its way to block, or gone without blocking
+        remove_timer(id);
+        self.processes[&self.tasks[id].pid].cancel_waits(id);
+        let task = &mut self.tasks[id];
+        task.task_status = TaskStatus::Exited;
+        task.exit_code = exit_code;
+        if id == current {
+            task.stats.charge_kernel(get_time_us());
+        }
+        let off_cpu = task.on_cpu.is_none();
+        let (user_time, kernel_time) = (task.stats.user_time, task.stats.kernel_time);
+        self.scheduler.remove(id);
+        let process = self.processes.get_mut(&task.pid).unwrap();
+        process.user_time += user_time;
+        process.kernel_time += kernel_time;
+        if off_cpu {
+            self.release_user_res(id);
+        }
+    }
+
+    /// The exited thread `id` is off its hart: unmap its user stack and
+    /// `TrapContext`, or the whole address space once the last thread of an
+    /// exited process is off.
+    fn release_user_res(&mut self, id: usize) {
+        let (pid, tid) = (self.tasks[id].pid, self.tasks[id].tid);
+        if self.processes[&pid].is_zombie {
+            if self.is_off_cpu(pid) {
+                let process = self.processes.get_mut(&pid).unwrap();
+                process.memory_set.recycle_data_pages();
+            }
+        } else if tid != 0 {
+            self.processes.get_mut(&pid).unwrap().dealloc_user_res(tid);
+            self.flush_remote_tlb(pid, 0, usize::MAX);
+        }
+    }
+
+    /// Process `pid` exits with all its threads and frees its address space.
+    /// Only the exit status is left for its parent, and its children become
+    /// orphans nobody will wait for.
+    fn exit_process(&mut self, pid: usize, exit_code: i32, reason: ExitReason) {
+        let thread_ids: Vec<usize> = self.processes[&pid].thread_ids().collect();
+        for id in thread_ids {
+            if self.tasks[id].task_status != TaskStatus::Exited {
+                self.exit_thread(id, exit_code);
+            }
+        }
+        let off_cpu = self.is_off_cpu(pid);
+        let process = self.processes.get_mut(&pid).unwrap();
+        if off_cpu {
+            process.memory_set.recycle_data_pages();
+        }
+        // closing our files lets the readers of our pipes see EOF
+        process.fd_table.clear();
+        process.is_zombie = true;
+        process.exit_code = exit_code;
+        process.exit_reason = reason;
+        let record = ExitRecord {
+            pid,
+            exit_code,
+            reason,
+            user_time: process.user_time,
+            kernel_time: process.kernel_time,
+        };
+        let children = core::mem::take(&mut process.children);
+        self.exit_log.push(record);
+        for child in children {
+            self.processes.get_mut(&child).unwrap().parent = None;
+        }
+    }
+
+    /// Take out the exited process `pid` together with what is left of its
+    /// threads. None of them may be on a hart. Freeing their address space
+    /// and kernel stacks takes other locks, so they are for the caller to
+    /// drop once ours is released.
+    fn reap_process(&mut self, pid: usize) -> (ProcessControlBlock, Vec<TaskControlBlock>) {
+        let process = self.processes.remove(&pid).unwrap();
+        let threads = process
+            .thread_ids()
+            .filter_map(|id| self.tasks.remove(id))
+            .collect();
+        (process, threads)
+    }
 }
 
 lazy_static! {
@@ -56,162 +321,710 @@ lazy_static! {
         info!("init TASK_MANAGER");
         let num_app = get_num_app();
         info!("num_app = {}", num_app);
-        let mut tasks: Vec<TaskControlBlock> = Vec::new();
+        let mut inner = TaskManagerInner {
+            tasks: TaskTable::new(),
+            processes: BTreeMap::new(),
+            scheduler: scheduler::new_scheduler(),
+            exit_log: ExitLog::default(),
+        };
         for i in 0..num_app {
-            tasks.push(TaskControlBlock::new(get_app_data(i), i));
+            let (process, thread) = ProcessControlBlock::new(get_app_data(i), None);
+            inner.add_process(process, thread);
         }
         TaskManager {
-            num_app,
-            inner: unsafe {
-                UPSafeCell::new(TaskManagerInner {
-                    tasks,
-                    current_task: 0,
-                })
-            },
+            inner: SpinLock::new(inner),
         }
     };
 }
-use crate::timer::get_time_us;
+use crate::timer::{get_time_us, remove_timer};
+use crate::trap::wait_for_interrupt;
 
 impl TaskManager {
-    /// Run the first task in task list.
+    /// The idle loop of this hart: switch to the next `Ready` task until
+    /// every task has exited, then power off. While only tasks that are
+    /// blocked or running elsewhere are left, the hart sleeps in `wfi` until
+    /// a timer interrupt wakes a sleeper or another hart kicks it.
     ///
-    /// Generally, the first task in task list is an idle task (we call it zero process later).
-    /// But in ch4, we load apps statically, so the first task is a real app.
-    fn run_first_task(&self) -> ! {
-        let mut inner = self.inner.exclusive_access();
-        let next_task = &mut inner.tasks[0];
-        next_task.task_status = TaskStatus::Running;
+    /// A task comes back here when it gives up the hart. Only then is it
+    /// handed back to the scheduler or its stacks released, as up to the
+    /// switch it still runs on them.
+    fn run_tasks(&self) -> ! {
+        loop {
+            self.release_exited_tasks();
+            let mut inner = self.inner.lock();
+            if let Some(next) = inner.pick_ready_task() {
+                let next_task = &mut inner.tasks[next];
+                next_task.task_status = TaskStatus::Running;
+                next_task.on_cpu = Some(hart_id());
+                next_task.stats.switch_in(get_time_us());
+                let next_task_cx_ptr = &next_task.task_cx as *const TaskContext;
+                drop(inner);
+                set_current(next);
+                // before this, we should drop local variables that must be dropped manually
+                unsafe {
+                    __switch(idle_task_cx_ptr(), next_task_cx_ptr);
+                }
+                let prev = take_current().unwrap();
+                self.put_prev_task(prev);
+            } else if inner
+                .tasks
+                .iter()
+                .all(|task| task.task_status == TaskStatus::Exited)
+            {
+                drop(inner);
+                self.finish();
+            } else {
+                set_idle(true);
+                drop(inner);
+                wait_for_interrupt();
+                set_idle(false);
+            }
+        }
+    }
 
-        next_task.stats.first_run_time = get_time_us();
+    /// Task `id` just switched back to the idle loop of this hart. Now that
+    /// it is off, a `Ready` task can go to the scheduler and an exited one
+    /// give up its user stack.
+    fn put_prev_task(&self, id: usize) {
+        let mut inner = self.inner.lock();
+        let task = &mut inner.tasks[id];
+        task.on_cpu = None;
+        match task.task_status {
+            TaskStatus::Ready => {
+                let priority = task.priority;
+                inner.scheduler.add(id, priority);
+            }
+            TaskStatus::Exited => inner.release_user_res(id),
+            _ => {}
+        }
+    }
 
-        let next_task_cx_ptr = &next_task.task_cx as *const TaskContext;
-        drop(inner);
-        let mut _unused = TaskContext::zero_init();
-        // before this, we should drop local variables that must be dropped manually
-        unsafe {
-            __switch(&mut _unused as *mut _, next_task_cx_ptr);
+    /// Change the status of current `Running` task into `Ready`, the idle
+    /// loop hands it back to the scheduler. `preempted` if its time slice ran
+    /// out rather than it yielding. An exited task stays exited.
+    fn mark_current_suspended(&self, preempted: bool) {
+        let mut inner = self.inner.lock();
+        let task = &mut inner.tasks[current_task_id()];
+        if task.task_status != TaskStatus::Running {
+            return;
+        }
+        task.task_status = TaskStatus::Ready;
+        if preempted {
+            task.stats.involuntary_switches += 1;
+        } else {
+            task.stats.voluntary_switches += 1;
+        }
+    }
+
+    /// Change the status of current `Running` task into `Blocked`. It is not
+    /// handed to the scheduler until [`TaskManager::wakeup_task`].
+    ///
+    /// Returns whether the task should switch away, which it need not if the
+    /// wakeup came first, from another hart.
+    fn mark_current_blocked(&self) -> bool {
+        let mut inner = self.inner.lock();
+        let current = current_task_id();
+        let fatal_signal = inner.has_fatal_signal(current);
+        let task = &mut inner.tasks[current];
+        if task.task_status != TaskStatus::Running {
+            return true;
+        }
+        if task.wakeup_pending {
+            task.wakeup_pending = false;
+            return false;
         }
-        panic!("unreachable in run_first_task!");
+        // `kill` would not wake us up for it, we die on our way out instead
+        if fatal_signal {
+            return false;
+        }
+        task.task_status = TaskStatus::Blocked;
+        task.stats.voluntary_switches += 1;
+        true
     }
 
-    /// Change the status of current `Running` task into `Ready`.
-    fn mark_current_suspended(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Ready;
+    /// See [`TaskManagerInner::wakeup_task`].
+    fn wakeup_task(&self, id: usize) {
+        self.inner.lock().wakeup_task(id);
     }
 
-    /// Change the status of current `Running` task into `Exited`.
-    fn mark_current_exited(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Exited;
+    /// Whether another thread took the process of the current task down
+    /// while it was running.
+    fn current_exited(&self) -> bool {
+        self.inner.lock().tasks[current_task_id()].task_status == TaskStatus::Exited
     }
 
-    /// Find next task to run and return task id.
+    /// Change the status of current `Running` task into `Exited`. We are
+    /// still running on its kernel stack, so that is released later by
+    /// [`TaskManager::release_exited_tasks`].
     ///
-    /// In this case, we only return the first `Ready` task in task list.
-    fn find_next_task(&self) -> Option<usize> {
-        let inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        (current + 1..current + self.num_app + 1)
-            .map(|id| id % self.num_app)
-            .find(|id| inner.tasks[*id].task_status == TaskStatus::Ready)
+    /// The main thread exiting, or any thread faulting, takes the whole
+    /// process down with it. Nothing happens if that already came from
+    /// another thread.
+    fn mark_current_exited(&self, exit_code: i32, reason: ExitReason) {
+        let mut inner = self.inner.lock();
+        let current = current_task_id();
+        if inner.tasks[current].task_status == TaskStatus::Exited {
+            return;
+        }
+        let (pid, tid) = (inner.tasks[current].pid, inner.tasks[current].tid);
+        if tid == 0 || reason != ExitReason::Normal {
+            inner.exit_process(pid, exit_code, reason);
+        } else {
+            inner.exit_thread(current, exit_code);
+        }
+    }
+
+    /// Unmap the kernel stacks of exited tasks and reap the processes no
+    /// parent will wait for. Tasks still on a hart are skipped: they may be
+    /// on their way out but still running on their stack.
+    fn release_exited_tasks(&self) {
+        let mut inner = self.inner.lock();
+        let mut kernel_stacks = Vec::new();
+        for task in inner.tasks.iter_mut() {
+            if task.on_cpu.is_none() && task.task_status == TaskStatus::Exited {
+                kernel_stacks.extend(task.kernel_stack.take());
+            }
+        }
+        let orphans: Vec<usize> = inner
+            .processes
+            .values()
+            .filter(|process| process.is_zombie && process.parent.is_none())
+            .map(|process| process.getpid())
+            .filter(|pid| inner.is_off_cpu(*pid))
+            .collect();
+        let mut reaped = Vec::new();
+        for pid in orphans {
+            reaped.push(inner.reap_process(pid));
+        }
+        // unmapping the kernel stacks takes `KERNEL_SPACE` and fences the
+        // other harts, so they and the reaped processes go without our lock
+        drop(inner);
+        drop(kernel_stacks);
+        drop(reaped);
     }
 
     /// Get the current 'Running' task's token.
     fn get_current_token(&self) -> usize {
-        let inner = self.inner.exclusive_access();
-        inner.tasks[inner.current_task].get_user_token()
+        let inner = self.inner.lock();
+        inner.current_process().get_user_token()
     }
 
     #[allow(clippy::mut_from_ref)]
     /// Get the current 'Running' task's trap contexts.
     fn get_current_trap_cx(&self) -> &mut TrapContext {
-        let inner = self.inner.exclusive_access();
-        inner.tasks[inner.current_task].get_trap_cx()
+        let inner = self.inner.lock();
+        inner.tasks[current_task_id()].get_trap_cx()
+    }
+
+    /// Where the current task's `TrapContext` is in its address space.
+    fn get_current_trap_cx_user_va(&self) -> usize {
+        let inner = self.inner.lock();
+        trap_cx_bottom_from_tid(inner.tasks[current_task_id()].tid)
     }
 
-    /// Switch current `Running` task to the task we have found,
-    /// or there is no `Ready` task and we can exit with all applications completed
+    /// Give up this hart: switch from the current task, whose status has
+    /// been changed already, to the idle loop, which runs the next one.
+    /// Returns when the current task is switched to again.
     fn run_next_task(&self) {
-        if let Some(next) = self.find_next_task() {
-            let mut inner = self.inner.exclusive_access();
-            let current = inner.current_task;
-            inner.tasks[next].task_status = TaskStatus::Running;
-            inner.current_task = next;
-
-            inner.tasks[next].stats.first_run_time = get_time_us();
-
-            let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
-            let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
-            drop(inner);
-            // before this, we should drop local variables that must be dropped manually
-            unsafe {
-                __switch(current_task_cx_ptr, next_task_cx_ptr);
-            }
-            // go back to user mode
-        } else {
-            panic!("All applications completed!");
+        let mut inner = self.inner.lock();
+        let current = current_task_id();
+        inner.tasks[current].stats.charge_kernel(get_time_us());
+        let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
+        drop(inner);
+        // before this, we should drop local variables that must be dropped manually
+        unsafe {
+            __switch(current_task_cx_ptr, idle_task_cx_ptr());
         }
+        // go back to user mode
+    }
+
+    /// Nothing is left to run: print what became of every task and power
+    /// off, with a failure status if any of them faulted. Running out of CPU
+    /// time is not a failure by itself: the parent sees it in the exit code.
+    fn finish(&self) -> ! {
+        let inner = self.inner.lock();
+        println!("[kernel] All applications completed!");
+        println!("[kernel]   pid  exit code  reason  user(us)  kernel(us)");
+        if inner.exit_log.dropped > 0 {
+            println!(
+                "[kernel] ({} earlier exits not shown)",
+                inner.exit_log.dropped
+            );
+        }
+        for record in inner.exit_log.records.iter() {
+            println!(
+                "[kernel] {:>5} {:>10}  {:<6} {:>9} {:>11}",
+                record.pid,
+                record.exit_code,
+                match record.reason {
+                    ExitReason::Normal => "exit",
+                    ExitReason::Fault => "fault",
+                    ExitReason::Killed => "killed",
+                    ExitReason::CpuLimit => "cpu",
+                },
+                record.user_time,
+                record.kernel_time
+            );
+        }
+        let failure = inner.exit_log.faults > 0;
+        drop(inner);
+        shutdown(failure)
     }
 
     #[allow(dead_code)]
     // TODO finish sys_tasks_info
     fn get_current_task_info(&self) -> (TaskStatus, [u32; MAX_SYSCALL_NUM], usize) {
-        let inner = self.inner.exclusive_access();
-        let status = inner.tasks[inner.current_task].task_status;
-        let (syscall_record, total_time) = inner.tasks[inner.current_task].stats.get_info();
+        let inner = self.inner.lock();
+        let status = inner.tasks[current_task_id()].task_status;
+        let (syscall_record, total_time) =
+            inner.tasks[current_task_id()].stats.get_info(get_time_us());
         (status, syscall_record, total_time)
     }
+
+    /// Accounting of the current task, with the time up to now charged.
+    fn get_current_task_stats(&self) -> TaskStatsInfo {
+        let mut inner = self.inner.lock();
+        let current = current_task_id();
+        inner.tasks[current].stats.charge_kernel(get_time_us());
+        inner.tasks[current].stats
+    }
+
+    /// Charge the time since the current task last entered user mode to it.
+    fn account_user_time(&self) {
+        let mut inner = self.inner.lock();
+        let current = current_task_id();
+        inner.tasks[current].stats.charge_user(get_time_us());
+    }
+
+    /// Charge the time the current task spent in the kernel since it
+    /// trapped in or was switched in.
+    fn account_kernel_time(&self) {
+        let mut inner = self.inner.lock();
+        let current = current_task_id();
+        inner.tasks[current].stats.charge_kernel(get_time_us());
+    }
     
-    fn record_syscall(&self, syscall_id: usize){
-        let mut inner = self.inner.exclusive_access();
-        let current_task = inner.current_task;
-        inner.tasks[current_task].stats.system_call_record[syscall_id]+=1;
+    /// Set the priority of the current task, the scheduler sees it the next
+    /// time the task becomes `Ready`.
+    fn set_current_priority(&self, priority: usize) {
+        let mut inner = self.inner.lock();
+        let current = current_task_id();
+        inner.tasks[current].priority = priority;
     }
 
-    #[allow(unused_variables)]
-    fn mmap(&self, start: usize, len:usize, port:usize) -> isize {
-        let mut inner = self.inner.exclusive_access();
-        let current_task = inner.current_task;
-        
-        let start_va = VirtAddr::from(start).floor();
-        let end_va = VirtAddr::from(start+len).ceil();
+    /// Replace the CPU time budget of the current task, returning the old
+    /// one.
+    fn set_current_cpu_limit(&self, limit_us: Option<usize>) -> Option<usize> {
+        let mut inner = self.inner.lock();
+        let task = &mut inner.tasks[current_task_id()];
+        core::mem::replace(&mut task.cpu_limit_us, limit_us)
+    }
 
-        for i in start_va.0..end_va.0 {
-            // i.into();
-            if inner.tasks[current_task].memory_set.is_all_map(crate::mm::VirtPageNum(i)) {
-                println!("[task::mod::TaskManager]there is a overlap");
-                return -1;
+    /// Whether the current task has used more CPU time than it may.
+    fn current_over_cpu_limit(&self) -> bool {
+        let inner = self.inner.lock();
+        let task = &inner.tasks[current_task_id()];
+        task.cpu_limit_us
+            .map_or(false, |limit| task.stats.cpu_time() > limit)
+    }
+
+    /// Tell the scheduler a timer tick passed, returns whether the current
+    /// task should be preempted.
+    fn scheduler_tick(&self) -> bool {
+        let mut inner = self.inner.lock();
+        let current = current_task_id();
+        inner.scheduler.tick(current)
+    }
+
+    /// Get the pid of the current task.
+    fn get_current_pid(&self) -> usize {
+        let inner = self.inner.lock();
+        inner.tasks[current_task_id()].pid
+    }
+
+    /// Get the tid of the current task inside its process.
+    fn get_current_tid(&self) -> usize {
+        let inner = self.inner.lock();
+        inner.tasks[current_task_id()].tid
+    }
+
+    /// Fork the current process, returning the pid of the child, or -1 if
+    /// the caller is not the only thread left.
+    ///
+    /// The address space is copied without our lock. Only the caller could
+    /// change it meanwhile, but it may be killed, in which case the child is
+    /// dropped.
+    fn fork_current(&self) -> isize {
+        let inner = self.inner.lock();
+        let current = current_task_id();
+        let pid = inner.tasks[current].pid;
+        if inner.tasks[current].tid != 0 || inner.has_other_live_threads(pid) {
+            return -1;
+        }
+        let source = inner.processes[&pid].fork_source(&inner.tasks[current]);
+        drop(inner);
+        let (child, thread) = ProcessControlBlock::fork(source);
+        self.add_child(child, thread)
+    }
+
+    /// Load the app `elf_data` as a new child of the current process,
+    /// returning the pid of the child, or -1 if the current process was
+    /// killed while loading it.
+    fn spawn(&self, elf_data: &[u8]) -> isize {
+        let (child, thread) = ProcessControlBlock::new(elf_data, Some(self.get_current_pid()));
+        self.add_child(child, thread)
+    }
+
+    /// Add `child`, built without our lock, as a child of the current
+    /// process, returning its pid, or -1 if the current process has exited
+    /// meanwhile.
+    fn add_child(&self, child: ProcessControlBlock, thread: TaskControlBlock) -> isize {
+        let mut inner = self.inner.lock();
+        if inner.current_process().is_zombie {
+            return -1;
+        }
+        let child_pid = inner.add_process(child, thread);
+        inner.current_process_mut().children.push(child_pid);
+        child_pid as isize
+    }
+
+    /// Replace the address space of the current process with the app
+    /// `elf_data`. Returns -1 if the caller is not the only thread left.
+    fn exec_current(&self, elf_data: &[u8]) -> isize {
+        // loading needs no lock, and only the caller could start a thread
+        let elf = MemorySet::from_elf(elf_data);
+        let mut inner = self.inner.lock();
+        let current = current_task_id();
+        let pid = inner.tasks[current].pid;
+        if inner.tasks[current].tid != 0 || inner.has_other_live_threads(pid) {
+            return -1;
+        }
+        // exited threads nobody waited for go with the old program
+        let zombie_ids: Vec<usize> = inner.processes[&pid]
+            .thread_ids()
+            .filter(|id| *id != current)
+            .collect();
+        let mut zombies = Vec::new();
+        for id in zombie_ids {
+            let thread = inner.tasks.remove(id).unwrap();
+            inner.current_process_mut().remove_thread(thread.tid);
+            zombies.push(thread);
+        }
+        let inner_ref = &mut *inner;
+        let process = inner_ref.processes.get_mut(&pid).unwrap();
+        // the old address space is freed on return, without our lock
+        let _old_memory_set = process.exec(elf, &mut inner_ref.tasks[current]);
+        drop(inner);
+        0
+    }
+
+    /// Reap an exited child of the current process, `pid == -1` for any
+    /// child.
+    ///
+    /// Returns the child's pid and stores its exit code, or -1 if there is no
+    /// such child or `exit_code_ptr` is bad, or -2 if it has not exited yet.
+    fn waitpid(&self, pid: isize, exit_code_ptr: *mut i32) -> isize {
+        let mut inner = self.inner.lock();
+        let wanted = |child_pid: usize| pid == -1 || pid as usize == child_pid;
+        let children: Vec<usize> = inner
+            .current_process()
+            .children
+            .iter()
+            .copied()
+            .filter(|child_pid| wanted(*child_pid))
+            .collect();
+        if children.is_empty() {
+            return -1;
+        }
+        // a thread of the child may still be getting off another hart
+        let zombie = children
+            .into_iter()
+            .find(|child_pid| inner.processes[child_pid].is_zombie && inner.is_off_cpu(*child_pid));
+        let child_pid = match zombie {
+            Some(child_pid) => child_pid,
+            None => return -2,
+        };
+        // check before reaping, or the exit code would be lost
+        let exit_code = if exit_code_ptr.is_null() {
+            None
+        } else {
+            let token = inner.current_process().get_user_token();
+            match translated_refmut(token, exit_code_ptr) {
+                Some(exit_code) => Some(exit_code),
+                None => return -1,
             }
+        };
+        // dropping the child frees its pid, and with its threads their
+        // kernel stacks and task ids
+        let child = inner.reap_process(child_pid);
+        inner
+            .current_process_mut()
+            .children
+            .retain(|pid| *pid != child_pid);
+        if let Some(exit_code) = exit_code {
+            *exit_code = child.0.exit_code;
         }
+        drop(inner);
+        child_pid as isize
+    }
 
-        println!("{}", port);
+    /// Start a thread of the current process at `entry` with `arg`,
+    /// returning its tid, or -1 if there is no room for its stack.
+    fn thread_create(&self, entry: usize, arg: usize) -> isize {
+        let mut inner = self.inner.lock();
+        let current = current_task_id();
+        let (priority, signal_mask) = (inner.tasks[current].priority, inner.tasks[current].signal_mask);
+        let cpu_limit_us = inner.tasks[current].cpu_limit_us;
+        let mut thread = match inner.current_process_mut().create_thread(entry, arg) {
+            Some(thread) => thread,
+            None => return -1,
+        };
+        thread.priority = priority;
+        thread.signal_mask = signal_mask;
+        thread.cpu_limit_us = cpu_limit_us;
+        let tid = thread.tid;
+        inner.add_task(thread);
+        tid as isize
+    }
 
-        // allow user to using this page in User mode 
-        let permission = MapPermission::from_bits(((port << 1) | 16) as u8);
-       // let a = MapPermission::from_bits((port << 1) as u8);
-        inner.tasks[current_task].memory_set.insert_framed_area(VirtAddr(start), VirtAddr(start+len), permission.unwrap());
-        
+    /// Reap the exited thread `tid` of the current process.
+    ///
+    /// Returns its exit code, or -1 if there is no such thread or it is the
+    /// caller, or -2 if it has not exited yet.
+    fn waittid(&self, tid: usize) -> isize {
+        let mut inner = self.inner.lock();
+        if inner.tasks[current_task_id()].tid == tid {
+            return -1;
+        }
+        let id = match inner.current_process().threads.get(tid).copied().flatten() {
+            Some(id) => id,
+            None => return -1,
+        };
+        if inner.tasks[id].task_status != TaskStatus::Exited || inner.tasks[id].on_cpu.is_some() {
+            return -2;
+        }
+        let thread = inner.tasks.remove(id).unwrap();
+        inner.current_process_mut().remove_thread(tid);
+        drop(inner);
+        thread.exit_code as isize
+    }
 
+    /// Send signal `signum` to the process `pid`. It goes to the first
+    /// thread that does not block it, or the main thread if all do, and is
+    /// delivered when that thread next returns to user mode.
+    ///
+    /// Returns -1 if there is no such signal or live process.
+    fn kill(&self, pid: usize, signum: usize) -> isize {
+        let mut inner = self.inner.lock();
+        let signal = match SignalFlags::from_signum(signum) {
+            Some(signal) => signal,
+            None => return -1,
+        };
+        let process = match inner.processes.get(&pid).filter(|process| !process.is_zombie) {
+            Some(process) => process,
+            None => return -1,
+        };
+        let live: Vec<usize> = process
+            .thread_ids()
+            .filter(|id| inner.tasks[*id].task_status != TaskStatus::Exited)
+            .collect();
+        let target = live
+            .iter()
+            .copied()
+            .find(|id| !inner.tasks[*id].signal_mask.contains(signal))
+            .or_else(|| live.first().copied());
+        let id = match target {
+            Some(id) => id,
+            None => return -1,
+        };
+        inner.tasks[id].signals |= signal;
+        // a blocked target would only see the signal once something else
+        // woke it up, so stop its wait if the signal is going to kill it
+        if inner.tasks[id].task_status == TaskStatus::Blocked && inner.has_fatal_signal(id) {
+            remove_timer(id);
+            inner.processes[&pid].cancel_waits(id);
+            inner.wakeup_task(id);
+        }
         0
     }
 
+    /// Set the action of signal `signum` for the current process if `action`
+    /// is given, and return the old one. `None` if there is no such signal
+    /// or it cannot be caught.
+    fn sigaction(&self, signum: usize, action: Option<SignalAction>) -> Option<SignalAction> {
+        SignalFlags::from_signum(signum).filter(|signal| !SignalFlags::UNCATCHABLE.contains(*signal))?;
+        let mut inner = self.inner.lock();
+        let process = inner.current_process_mut();
+        let old = process.signal_actions[signum];
+        if let Some(mut action) = action {
+            action.mask.remove(SignalFlags::UNCATCHABLE);
+            process.signal_actions[signum] = action;
+        }
+        Some(old)
+    }
+
+    /// Replace the blocked signals of the current task, returning the old
+    /// set. `SIGKILL` and `SIGSTOP` cannot be blocked.
+    fn sigprocmask(&self, mask: SignalFlags) -> SignalFlags {
+        let mut inner = self.inner.lock();
+        let current = current_task_id();
+        let task = &mut inner.tasks[current];
+        let old = task.signal_mask;
+        task.signal_mask = mask - SignalFlags::UNCATCHABLE;
+        old
+    }
+
+    /// Put back what the running signal handler interrupted. Returns the
+    /// restored `a0`, so the syscall return does not clobber it, or -1 if no
+    /// handler is running.
+    fn sigreturn(&self) -> isize {
+        let mut inner = self.inner.lock();
+        let current = current_task_id();
+        let task = &mut inner.tasks[current];
+        match task.trap_ctx_backup.take() {
+            Some((trap_cx, mask)) => {
+                *task.get_trap_cx() = trap_cx;
+                task.signal_mask = mask;
+                task.handling_sig = None;
+                trap_cx.x[10] as isize
+            }
+            None => -1,
+        }
+    }
+
+    /// Act on the pending signals the current task does not block. A caught
+    /// signal rewrites the trap context to enter its handler, which returns
+    /// to the `sigreturn` trampoline. While a handler runs, other caught
+    /// signals stay pending.
+    ///
+    /// Returns the signal that kills the process, if any.
+    fn deliver_signals(&self) -> Option<usize> {
+        let mut inner = self.inner.lock();
+        let current = current_task_id();
+        let actions = inner.current_process().signal_actions;
+        let task = &mut inner.tasks[current];
+        let mut deliverable = task.signals - (task.signal_mask - SignalFlags::UNCATCHABLE);
+        while let Some(signum) = deliverable.first_signum() {
+            let signal = SignalFlags::from_signum(signum).unwrap();
+            deliverable.remove(signal);
+            let action = actions[signum];
+            if action.handler == SIG_DFL {
+                task.signals.remove(signal);
+                if SignalFlags::DEFAULT_IGNORED.contains(signal) {
+                    continue;
+                }
+                return Some(signum);
+            }
+            if action.handler == SIG_IGN {
+                task.signals.remove(signal);
+                continue;
+            }
+            if task.handling_sig.is_some() {
+                continue;
+            }
+            task.signals.remove(signal);
+            let trap_cx = task.get_trap_cx();
+            task.trap_ctx_backup = Some((*trap_cx, task.signal_mask));
+            task.handling_sig = Some(signum);
+            task.signal_mask |= action.mask | signal;
+            trap_cx.sepc = action.handler;
+            trap_cx.x[1] = SIGRETURN_TRAMPOLINE;
+            trap_cx.x[10] = signum;
+        }
+        None
+    }
+
+    /// The current task hit a fault that raises `signal`. Make it pending
+    /// if a handler can take it right away, and return whether it did:
+    /// otherwise returning to the faulting instruction would just fault
+    /// again.
+    fn raise_fault(&self, signal: SignalFlags) -> bool {
+        let mut inner = self.inner.lock();
+        let current = current_task_id();
+        let signum = signal.first_signum().unwrap();
+        let handler = inner.current_process().signal_actions[signum].handler;
+        let task = &mut inner.tasks[current];
+        let catchable = handler != SIG_DFL
+            && handler != SIG_IGN
+            && !task.signal_mask.contains(signal)
+            && task.handling_sig.is_none();
+        if catchable {
+            task.signals |= signal;
+        }
+        catchable
+    }
+
+    /// Crash report of the current task, see [`core_dump`].
+    fn current_core_dump(&self, scause: usize, stval: usize) -> String {
+        let inner = self.inner.lock();
+        let task = &inner.tasks[current_task_id()];
+        core_dump(
+            task.pid,
+            task.tid,
+            hart_id(),
+            scause,
+            stval,
+            task.get_trap_cx(),
+            &inner.current_process().memory_set,
+        )
+    }
+
+    fn record_syscall(&self, syscall_id: usize){
+        let mut inner = self.inner.lock();
+        let current_task = current_task_id();
+        inner.tasks[current_task].stats.system_call_record[syscall_id]+=1;
+    }
+
+    /// Map `len` bytes at `start`. With `fixed` it is that address or
+    /// nothing; otherwise `start` is a hint (0 for none) and the kernel finds
+    /// room elsewhere if it is taken.
+    ///
+    /// Returns the mapped address, or -1 if no free user range was found.
+    fn mmap(&self, start: usize, len:usize, port:usize, fixed: bool, shared: bool) -> isize {
+        let mut inner = self.inner.lock();
+        let memory_set = &mut inner.current_process_mut().memory_set;
+
+        let hint_is_free = start != 0
+            && start.checked_add(len).map_or(false, |end| {
+                memory_set.is_free_user_range(VirtAddr::from(start), VirtAddr::from(end))
+            });
+        let start_va = if hint_is_free {
+            VirtAddr::from(start)
+        } else if fixed {
+            debug!("[kernel] mmap: {:#x} overlaps a mapped area", start);
+            return -1;
+        } else {
+            match memory_set.find_free_range(len, PAGE_SIZE) {
+                Some(va) => va,
+                None => return -1,
+            }
+        };
+        let end_va = VirtAddr::from(start_va.0 + len);
+
+        // allow user to using this page in User mode 
+        let permission = MapPermission::from_bits(((port << 1) | 16) as u8).unwrap();
+        if shared {
+            memory_set.insert_shared_area(start_va, end_va, permission);
+        } else {
+            memory_set.insert_framed_area(start_va, end_va, permission);
+        }
+
+        start_va.0 as isize
+    }
+
     fn unmap(&self, start: usize, len: usize) -> isize {
-        let mut inner = self.inner.exclusive_access();
-        let current_task = inner.current_task;
+        let mut inner = self.inner.lock();
+        let memory_set = &mut inner.current_process_mut().memory_set;
 
         // println!("start:{}; len:{}", start, len);
 
         let start_va = VirtAddr::from(start).floor();
         let end_va = VirtAddr::from(start+len).ceil();
-        print!("start:{}, end:{}", start_va.0, end_va.0);
+        trace!("[kernel] munmap: vpn {:#x}..{:#x}", start_va.0, end_va.0);
         
         for i in start_va.0..end_va.0 {
-            if inner.tasks[current_task].memory_set.is_not_all_map(crate::mm::VirtPageNum(i)){
-                println!("[TaskManager::umap]:  there is a variable haven't been map");
+            if memory_set.is_not_all_map(crate::mm::VirtPageNum(i)){
+                debug!("[kernel] munmap: vpn {:#x} is not mapped", i);
                 return -1;
             }
         }
@@ -233,8 +1046,10 @@ impl TaskManager {
         */
 
         for i in start_va.0..end_va.0 {
-            inner.tasks[current_task].memory_set.remove_map_area(VirtPageNum(i));
+            memory_set.remove_map_area(VirtPageNum(i));
         }
+        let pid = inner.tasks[current_task_id()].pid;
+        inner.flush_remote_tlb(pid, start, len);
         // for i in start_va.0..end_va.0 {
         //     if !inner.tasks[current_task].memory_set.unmap(VirtPageNum(i)) {
         //         return -1;
@@ -248,39 +1063,134 @@ impl TaskManager {
 
 }
 
-/// Run the first task in task list.
-pub fn run_first_task() {
-    TASK_MANAGER.run_first_task();
+/// Run tasks on this hart until all of them have exited.
+pub fn run_tasks() -> ! {
+    TASK_MANAGER.run_tasks()
 }
 
-/// Switch current `Running` task to the task we have found,
-/// or there is no `Ready` task and we can exit with all applications completed
+/// Switch current `Running` task to the idle loop, which runs the next
+/// task, or powers off once all applications completed
 fn run_next_task() {
     TASK_MANAGER.run_next_task();
 }
 
 /// Change the status of current `Running` task into `Ready`.
-fn mark_current_suspended() {
-    TASK_MANAGER.mark_current_suspended();
+fn mark_current_suspended(preempted: bool) {
+    TASK_MANAGER.mark_current_suspended(preempted);
 }
 
 /// Change the status of current `Running` task into `Exited`.
-fn mark_current_exited() {
-    TASK_MANAGER.mark_current_exited();
+fn mark_current_exited(exit_code: i32, reason: ExitReason) {
+    TASK_MANAGER.mark_current_exited(exit_code, reason);
 }
 
 /// Suspend the current 'Running' task and run the next task in task list.
 pub fn suspend_current_and_run_next() {
-    mark_current_suspended();
+    mark_current_suspended(false);
+    run_next_task();
+}
+
+/// Preempt the current 'Running' task, whose time slice is used up, and run
+/// the next task in task list.
+pub fn preempt_current_and_run_next() {
+    mark_current_suspended(true);
+    run_next_task();
+}
+
+/// Block the current 'Running' task until [`wakeup_task`] and run the next
+/// task in task list.
+pub fn block_current_and_run_next() {
+    if mark_current_blocked() {
+        run_next_task();
+    }
+}
+
+/// Change the status of current `Running` task into `Blocked`, returns
+/// whether it has to switch away.
+fn mark_current_blocked() -> bool {
+    TASK_MANAGER.mark_current_blocked()
+}
+
+/// Make the `Blocked` task `id` `Ready` again.
+pub fn wakeup_task(id: usize) {
+    TASK_MANAGER.wakeup_task(id);
+}
+
+/// Exit the current 'Running' task, and its process if it is the main
+/// thread, and run the next task in task list.
+pub fn exit_current_and_run_next(exit_code: i32) {
+    mark_current_exited(exit_code, ExitReason::Normal);
     run_next_task();
 }
 
-/// Exit the current 'Running' task and run the next task in task list.
-pub fn exit_current_and_run_next() {
-    mark_current_exited();
+/// Kill the current 'Running' task's process with signal `signum` and run
+/// the next task in task list. Its exit code is `-signum`.
+pub fn kill_current_and_run_next(signum: usize) {
+    mark_current_exited(-(signum as i32), signal::exit_reason_of(signum));
     run_next_task();
 }
 
+/// Terminate the process of the current 'Running' task if the task used up
+/// its CPU time budget, and run the next task. Checked on every timer tick.
+/// The exit code is `-SIGXCPU`, but the signal cannot be caught.
+pub fn enforce_cpu_limit() {
+    if TASK_MANAGER.current_over_cpu_limit() {
+        let signum = SignalFlags::SIGXCPU.first_signum().unwrap();
+        mark_current_exited(-(signum as i32), ExitReason::CpuLimit);
+        run_next_task();
+    }
+}
+
+/// Replace the CPU time budget of the current 'Running' task.
+pub fn set_current_cpu_limit(limit_us: Option<usize>) -> Option<usize> {
+    TASK_MANAGER.set_current_cpu_limit(limit_us)
+}
+
+/// Raise `signal` for a fault of the current 'Running' task, which trapped
+/// with `scause` and `stval`. If no handler can take it, a crash report is
+/// printed, the process is killed and the next task runs.
+pub fn raise_fault_signal(signal: SignalFlags, scause: usize, stval: usize) {
+    if !TASK_MANAGER.raise_fault(signal) {
+        print!("{}", TASK_MANAGER.current_core_dump(scause, stval));
+        kill_current_and_run_next(signal.first_signum().unwrap());
+    }
+}
+
+/// Deliver the pending signals of the current 'Running' task, right before
+/// it returns to user mode. If one of them kills it, the next task runs.
+///
+/// This is also where a thread stops whose process was taken down by a
+/// thread on another hart.
+pub fn handle_signals() {
+    if TASK_MANAGER.current_exited() {
+        run_next_task();
+    }
+    if let Some(signum) = TASK_MANAGER.deliver_signals() {
+        kill_current_and_run_next(signum);
+    }
+}
+
+/// Send signal `signum` to the process `pid`.
+pub fn kill(pid: usize, signum: usize) -> isize {
+    TASK_MANAGER.kill(pid, signum)
+}
+
+/// Get and maybe set the action of signal `signum` for the current
+/// 'Running' task's process.
+pub fn sigaction(signum: usize, action: Option<SignalAction>) -> Option<SignalAction> {
+    TASK_MANAGER.sigaction(signum, action)
+}
+
+/// Replace the blocked signals of the current 'Running' task.
+pub fn sigprocmask(mask: SignalFlags) -> SignalFlags {
+    TASK_MANAGER.sigprocmask(mask)
+}
+
+/// Return from the signal handler the current 'Running' task is in.
+pub fn sigreturn() -> isize {
+    TASK_MANAGER.sigreturn()
+}
+
 /// Get the current 'Running' task's token.
 pub fn current_user_token() -> usize {
     TASK_MANAGER.get_current_token()
@@ -291,6 +1201,12 @@ pub fn current_trap_cx() -> &'static mut TrapContext {
     TASK_MANAGER.get_current_trap_cx()
 }
 
+/// Get the address of the current 'Running' task's trap context in its
+/// address space.
+pub fn current_trap_cx_user_va() -> usize {
+    TASK_MANAGER.get_current_trap_cx_user_va()
+}
+
 // return task information
 pub fn get_task_info() -> (TaskStatus, [u32; MAX_SYSCALL_NUM], usize){
     TASK_MANAGER.get_current_task_info()
@@ -300,10 +1216,96 @@ pub fn record_syscall(syscall_id: usize){
     TASK_MANAGER.record_syscall(syscall_id);
 }
 
-pub fn mmap(start:usize, len:usize, port:usize) -> isize {
-    TASK_MANAGER.mmap(start, len, port)
+pub fn mmap(start:usize, len:usize, port:usize, fixed: bool, shared: bool) -> isize {
+    TASK_MANAGER.mmap(start, len, port, fixed, shared)
 }
 
 pub fn unmap(start: usize, len:usize) -> isize{
     TASK_MANAGER.unmap(start, len)
-}
\ No newline at end of file
+}
+
+/// Set the priority of the current 'Running' task.
+pub fn set_current_priority(priority: usize) {
+    TASK_MANAGER.set_current_priority(priority);
+}
+
+/// Account a timer tick to the current 'Running' task, returns whether its
+/// time slice is used up.
+pub fn scheduler_tick() -> bool {
+    TASK_MANAGER.scheduler_tick()
+}
+
+/// Get the pid of the current 'Running' task.
+pub fn current_pid() -> usize {
+    TASK_MANAGER.get_current_pid()
+}
+
+/// Get the tid of the current 'Running' task inside its process.
+pub fn current_tid() -> usize {
+    TASK_MANAGER.get_current_tid()
+}
+
+/// Fork the current 'Running' task's process, returning the pid of the child.
+pub fn fork_current() -> isize {
+    TASK_MANAGER.fork_current()
+}
+
+/// Start the app `elf_data` as a child of the current 'Running' task.
+pub fn spawn(elf_data: &[u8]) -> isize {
+    TASK_MANAGER.spawn(elf_data)
+}
+
+/// Replace the current 'Running' task's program with `elf_data`.
+pub fn exec_current(elf_data: &[u8]) -> isize {
+    TASK_MANAGER.exec_current(elf_data)
+}
+
+/// Reap an exited child of the current 'Running' task.
+pub fn waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+    TASK_MANAGER.waitpid(pid, exit_code_ptr)
+}
+
+/// Start a new thread in the current 'Running' task's process.
+pub fn thread_create(entry: usize, arg: usize) -> isize {
+    TASK_MANAGER.thread_create(entry, arg)
+}
+
+/// Reap an exited thread of the current 'Running' task's process.
+pub fn waittid(tid: usize) -> isize {
+    TASK_MANAGER.waittid(tid)
+}
+
+/// Run `f` on the current 'Running' task's process. `f` must not block or
+/// switch tasks: the task manager is borrowed meanwhile.
+pub fn with_current_process<T>(f: impl FnOnce(&mut ProcessControlBlock) -> T) -> T {
+    let mut inner = TASK_MANAGER.inner.lock();
+    f(inner.current_process_mut())
+}
+
+/// Run `f` on the process `pid` unless it has exited or does not exist.
+/// `f` must not block or switch tasks either.
+pub fn with_process<T>(pid: usize, f: impl FnOnce(&mut ProcessControlBlock) -> T) -> Option<T> {
+    let mut inner = TASK_MANAGER.inner.lock();
+    inner
+        .processes
+        .get_mut(&pid)
+        .filter(|process| !process.is_zombie)
+        .map(f)
+}
+
+/// Accounting of the current 'Running' task.
+pub fn current_task_stats() -> TaskStatsInfo {
+    TASK_MANAGER.get_current_task_stats()
+}
+
+/// Charge the time since the current 'Running' task entered user mode to
+/// its user time. Called when a trap comes in.
+pub fn account_user_time() {
+    TASK_MANAGER.account_user_time();
+}
+
+/// Charge the time since the last boundary to the current 'Running' task's
+/// kernel time. Called right before returning to user mode.
+pub fn account_kernel_time() {
+    TASK_MANAGER.account_kernel_time();
+}
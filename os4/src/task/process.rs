@@ -0,0 +1,339 @@
+//! Processes: what the threads of one program share
+//!
+//! A process owns the address space, its place in the process tree and the
+//! exit status its parent collects. Registers and stacks belong to its
+//! threads, one [`TaskControlBlock`] each. Thread `tid` keeps its user stack
+//! at [`ustack_bottom_from_tid`] and its `TrapContext` at
+//! [`trap_cx_bottom_from_tid`]. Thread 0 gets both from `MemorySet::from_elf`,
+//! the others map their own in [`ProcessControlBlock::alloc_user_res`].
+
+use super::id::RecycleAllocator;
+use super::mailbox::Mailbox;
+use super::{
+    pid_alloc, ExitReason, PidHandle, SignalAction, SignalFlags, TaskControlBlock, MAX_SIG, SIG_IGN,
+};
+use crate::config::{PAGE_SIZE, TRAP_CONTEXT, USER_STACK_SIZE};
+use crate::fs::{File, Stderr, Stdin, Stdout};
+use crate::mm::{MapPermission, MemorySet, MemorySnapshot, PhysPageNum, VirtAddr, KERNEL_SPACE};
+use crate::sync::{Condvar, DeadlockDetector, Mutex, Resource, Semaphore};
+use crate::trap::{trap_handler, TrapContext};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// where the `TrapContext` of thread `tid` lives
+pub fn trap_cx_bottom_from_tid(tid: usize) -> usize {
+    TRAP_CONTEXT - tid * PAGE_SIZE
+}
+
+/// bottom of the user stack of thread `tid`, there is a guard page between
+/// the stacks of two threads
+pub fn ustack_bottom_from_tid(ustack_base: usize, tid: usize) -> usize {
+    ustack_base + tid * (PAGE_SIZE + USER_STACK_SIZE)
+}
+
+/// What `fork` takes from the parent and its main thread, cheap to take.
+/// [`ProcessControlBlock::fork`] builds the child from it without the parent
+/// at hand.
+pub struct ForkSource {
+    memory: MemorySnapshot,
+    ustack_base: usize,
+    parent: usize,
+    signal_actions: [SignalAction; MAX_SIG + 1],
+    fd_table: Vec<Option<Arc<dyn File>>>,
+    priority: usize,
+    cpu_limit_us: Option<usize>,
+    signal_mask: SignalFlags,
+}
+
+/// process control block structure
+pub struct ProcessControlBlock {
+    pub pid: PidHandle,
+    pub memory_set: MemorySet,
+    /// bottom of the user stack of thread 0
+    pub ustack_base: usize,
+    /// pid of the process that created us, `None` if started by the kernel
+    /// or orphaned. Nobody waits for such a process, it is reaped right
+    /// after exit
+    pub parent: Option<usize>,
+    /// pids of our children, including exited ones not yet waited for
+    pub children: Vec<usize>,
+    /// task ids of our threads indexed by tid, `None` once reaped
+    pub threads: Vec<Option<usize>>,
+    tid_allocator: RecycleAllocator,
+    /// all threads are gone, only the exit status is left
+    pub is_zombie: bool,
+    pub exit_code: i32,
+    pub exit_reason: ExitReason,
+    /// CPU time of the threads that have exited, in microseconds
+    pub user_time: usize,
+    pub kernel_time: usize,
+    /// synchronization primitives of our threads, indexed by the ids user
+    /// programs know them by
+    pub mutex_list: Vec<Option<Arc<dyn Mutex>>>,
+    pub semaphore_list: Vec<Option<Arc<Semaphore>>>,
+    pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    /// who holds and waits for which mutexes and semaphores
+    pub deadlock: DeadlockDetector,
+    /// what each signal does to our threads, indexed by signal number
+    pub signal_actions: [SignalAction; MAX_SIG + 1],
+    /// messages other processes sent us
+    pub mailbox: Mailbox,
+    /// open files by file descriptor, shared by our threads
+    pub fd_table: Vec<Option<Arc<dyn File>>>,
+}
+
+/// Put `item` in the first free slot of `list`, returning its index.
+fn insert_in_free_slot<T>(list: &mut Vec<Option<T>>, item: T) -> usize {
+    if let Some(id) = list.iter().position(|slot| slot.is_none()) {
+        list[id] = Some(item);
+        id
+    } else {
+        list.push(Some(item));
+        list.len() - 1
+    }
+}
+
+impl ProcessControlBlock {
+    pub fn getpid(&self) -> usize {
+        self.pid.0
+    }
+    pub fn get_user_token(&self) -> usize {
+        self.memory_set.token()
+    }
+    fn with_memory_set(memory_set: MemorySet, ustack_base: usize, parent: Option<usize>) -> Self {
+        Self {
+            pid: pid_alloc(),
+            memory_set,
+            ustack_base,
+            parent,
+            children: Vec::new(),
+            threads: Vec::new(),
+            tid_allocator: RecycleAllocator::new(0),
+            is_zombie: false,
+            exit_code: 0,
+            exit_reason: ExitReason::Normal,
+            user_time: 0,
+            kernel_time: 0,
+            mutex_list: Vec::new(),
+            semaphore_list: Vec::new(),
+            condvar_list: Vec::new(),
+            deadlock: DeadlockDetector::default(),
+            signal_actions: [SignalAction::default(); MAX_SIG + 1],
+            mailbox: Mailbox::default(),
+            fd_table: vec![
+                // 0 -> stdin
+                Some(Arc::new(Stdin)),
+                // 1 -> stdout
+                Some(Arc::new(Stdout)),
+                // 2 -> stderr
+                Some(Arc::new(Stderr)),
+            ],
+        }
+    }
+    fn trap_cx_ppn(&self, tid: usize) -> PhysPageNum {
+        self.memory_set
+            .translate(VirtAddr::from(trap_cx_bottom_from_tid(tid)).into())
+            .unwrap()
+            .ppn()
+    }
+    /// A new thread with a fresh tid, whose stack and trap context are
+    /// already mapped.
+    fn new_thread(&mut self, tid: usize) -> TaskControlBlock {
+        let thread = TaskControlBlock::new(self.getpid(), tid, self.trap_cx_ppn(tid));
+        if tid >= self.threads.len() {
+            self.threads.resize(tid + 1, None);
+        }
+        self.threads[tid] = Some(thread.id());
+        thread
+    }
+    /// Load `elf_data` as a new process, returning it with its main thread.
+    pub fn new(elf_data: &[u8], parent: Option<usize>) -> (Self, TaskControlBlock) {
+        // memory_set with elf program headers/trampoline/trap context/user stack
+        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let mut process = Self::with_memory_set(memory_set, user_sp - USER_STACK_SIZE, parent);
+        let tid = process.tid_allocator.alloc();
+        let thread = process.new_thread(tid);
+        // prepare TrapContext in user space
+        *thread.get_trap_cx() = TrapContext::app_init_context(
+            entry_point,
+            user_sp,
+            KERNEL_SPACE.lock().token(),
+            thread.kernel_stack_top(),
+            trap_handler as usize,
+        );
+        (process, thread)
+    }
+    /// What a `fork` by our main thread `thread` takes from us.
+    pub fn fork_source(&self, thread: &TaskControlBlock) -> ForkSource {
+        assert_eq!(thread.tid, 0, "only the main thread may fork");
+        ForkSource {
+            memory: self.memory_set.snapshot(),
+            ustack_base: self.ustack_base,
+            parent: self.getpid(),
+            signal_actions: self.signal_actions,
+            fd_table: self.fd_table.clone(),
+            priority: thread.priority,
+            cpu_limit_us: thread.cpu_limit_us,
+            signal_mask: thread.signal_mask,
+        }
+    }
+    /// A copy of the process `source` was taken from, with its own pid and
+    /// address space. The child sees `fork` return 0.
+    pub fn fork(source: ForkSource) -> (Self, TaskControlBlock) {
+        let memory_set = MemorySet::from_existed_user(&source.memory);
+        let mut child = Self::with_memory_set(memory_set, source.ustack_base, Some(source.parent));
+        let tid = child.tid_allocator.alloc();
+        child.signal_actions = source.signal_actions;
+        child.fd_table = source.fd_table;
+        let mut child_thread = child.new_thread(tid);
+        child_thread.priority = source.priority;
+        child_thread.cpu_limit_us = source.cpu_limit_us;
+        child_thread.signal_mask = source.signal_mask;
+        // the TrapContext was copied along with the address space
+        let trap_cx = child_thread.get_trap_cx();
+        trap_cx.kernel_sp = child_thread.kernel_stack_top();
+        trap_cx.x[10] = 0;
+        (child, child_thread)
+    }
+    /// Replace our address space with `memory_set` fresh from
+    /// `MemorySet::from_elf`, returning the old one.
+    /// `thread` must be the main thread and the only one left.
+    pub fn exec(
+        &mut self,
+        (memory_set, user_sp, entry_point): (MemorySet, usize, usize),
+        thread: &mut TaskControlBlock,
+    ) -> MemorySet {
+        assert_eq!(thread.tid, 0, "only the main thread may exec");
+        let old_memory_set = core::mem::replace(&mut self.memory_set, memory_set);
+        self.ustack_base = user_sp - USER_STACK_SIZE;
+        // the handlers are gone with the old program, ignored signals stay so
+        for action in self.signal_actions.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+        thread.handling_sig = None;
+        thread.trap_ctx_backup = None;
+        thread.trap_cx_ppn = self.trap_cx_ppn(0);
+        *thread.get_trap_cx() = TrapContext::app_init_context(
+            entry_point,
+            user_sp,
+            KERNEL_SPACE.lock().token(),
+            thread.kernel_stack_top(),
+            trap_handler as usize,
+        );
+        old_memory_set
+    }
+    /// Start a thread at `entry` with `arg` in `a0`, on a stack of its own.
+    /// Returns `None` if the stack would collide with an existing mapping.
+    pub fn create_thread(&mut self, entry: usize, arg: usize) -> Option<TaskControlBlock> {
+        let tid = self.tid_allocator.alloc();
+        if !self.alloc_user_res(tid) {
+            self.tid_allocator.dealloc(tid);
+            return None;
+        }
+        let thread = self.new_thread(tid);
+        let ustack_top = ustack_bottom_from_tid(self.ustack_base, tid) + USER_STACK_SIZE;
+        let trap_cx = thread.get_trap_cx();
+        *trap_cx = TrapContext::app_init_context(
+            entry,
+            ustack_top,
+            KERNEL_SPACE.lock().token(),
+            thread.kernel_stack_top(),
+            trap_handler as usize,
+        );
+        trap_cx.x[10] = arg;
+        Some(thread)
+    }
+    /// Map the user stack and the `TrapContext` of thread `tid`.
+    fn alloc_user_res(&mut self, tid: usize) -> bool {
+        let ustack_bottom = ustack_bottom_from_tid(self.ustack_base, tid);
+        let ustack_top = ustack_bottom + USER_STACK_SIZE;
+        if !self
+            .memory_set
+            .is_free_user_range(ustack_bottom.into(), ustack_top.into())
+        {
+            return false;
+        }
+        self.memory_set.insert_framed_area(
+            ustack_bottom.into(),
+            ustack_top.into(),
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        );
+        let trap_cx_bottom = trap_cx_bottom_from_tid(tid);
+        self.memory_set.insert_framed_area(
+            trap_cx_bottom.into(),
+            (trap_cx_bottom + PAGE_SIZE).into(),
+            MapPermission::R | MapPermission::W,
+        );
+        true
+    }
+    /// Unmap the user stack and the `TrapContext` of the exited thread `tid`.
+    /// Its tid stays taken until [`ProcessControlBlock::remove_thread`].
+    pub fn dealloc_user_res(&mut self, tid: usize) {
+        let ustack_bottom = ustack_bottom_from_tid(self.ustack_base, tid);
+        self.memory_set
+            .remove_area_with_start_vpn(VirtAddr::from(ustack_bottom).into());
+        self.memory_set
+            .remove_area_with_start_vpn(VirtAddr::from(trap_cx_bottom_from_tid(tid)).into());
+    }
+    /// Forget the reaped thread `tid` and free its tid.
+    pub fn remove_thread(&mut self, tid: usize) {
+        self.threads[tid] = None;
+        self.tid_allocator.dealloc(tid);
+        self.deadlock.remove_thread(tid);
+    }
+    pub fn add_mutex(&mut self, mutex: Arc<dyn Mutex>) -> usize {
+        let id = insert_in_free_slot(&mut self.mutex_list, mutex);
+        self.deadlock.add_resource(Resource::Mutex(id), 1);
+        id
+    }
+    pub fn add_semaphore(&mut self, semaphore: Arc<Semaphore>, res_count: usize) -> usize {
+        let id = insert_in_free_slot(&mut self.semaphore_list, semaphore);
+        self.deadlock
+            .add_resource(Resource::Semaphore(id), res_count);
+        id
+    }
+    pub fn add_condvar(&mut self, condvar: Arc<Condvar>) -> usize {
+        insert_in_free_slot(&mut self.condvar_list, condvar)
+    }
+    /// Take our thread with task id `id` out of every wait queue of our
+    /// mutexes, semaphores and condvars.
+    pub fn cancel_waits(&self, id: usize) {
+        for mutex in self.mutex_list.iter().flatten() {
+            mutex.cancel_wait(id);
+        }
+        for semaphore in self.semaphore_list.iter().flatten() {
+            semaphore.cancel_wait(id);
+        }
+        for condvar in self.condvar_list.iter().flatten() {
+            condvar.cancel_wait(id);
+        }
+    }
+    pub fn get_mutex(&self, id: usize) -> Option<Arc<dyn Mutex>> {
+        self.mutex_list.get(id).cloned().flatten()
+    }
+    pub fn get_semaphore(&self, id: usize) -> Option<Arc<Semaphore>> {
+        self.semaphore_list.get(id).cloned().flatten()
+    }
+    pub fn get_condvar(&self, id: usize) -> Option<Arc<Condvar>> {
+        self.condvar_list.get(id).cloned().flatten()
+    }
+    /// the file behind file descriptor `fd`, if it is open
+    pub fn get_file(&self, fd: usize) -> Option<Arc<dyn File>> {
+        self.fd_table.get(fd).cloned().flatten()
+    }
+    /// Open the lowest free file descriptor for `file`.
+    pub fn alloc_fd(&mut self, file: Arc<dyn File>) -> usize {
+        insert_in_free_slot(&mut self.fd_table, file)
+    }
+    /// the tid of our thread with task id `id`
+    pub fn tid_of(&self, id: usize) -> Option<usize> {
+        self.threads.iter().position(|thread| *thread == Some(id))
+    }
+    /// task ids of the threads not reaped yet
+    pub fn thread_ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.threads.iter().flatten().copied()
+    }
+}
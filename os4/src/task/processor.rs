@@ -0,0 +1,98 @@
+//! Per-hart state
+//!
+//! Every hart runs its own idle loop, [`super::run_tasks`], on its boot
+//! stack. A task gives up its hart by switching back to that loop, which
+//! picks the next one, or waits in `wfi` if there is none. The hart id is kept in `tp`, which user code may
+//! clobber, so `__alltraps` loads it back from the `TrapContext`.
+
+use super::TaskContext;
+use crate::config::MAX_HARTS;
+use crate::sbi::send_ipi;
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::*;
+
+/// what a hart is doing
+pub struct Processor {
+    /// id of the task running here, `None` while in the idle loop
+    current: Option<usize>,
+    /// where the idle loop is while a task runs
+    idle_task_cx: TaskContext,
+}
+
+impl Processor {
+    fn new() -> Self {
+        Self {
+            current: None,
+            idle_task_cx: TaskContext::zero_init(),
+        }
+    }
+}
+
+lazy_static! {
+    /// one `Processor` per hart, only touched by that hart
+    static ref PROCESSORS: Vec<UPSafeCell<Processor>> = (0..MAX_HARTS)
+        .map(|_| unsafe { UPSafeCell::new(Processor::new()) })
+        .collect();
+}
+
+/// bit `i` is set while hart `i` waits in its idle loop for a task
+static IDLE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+/// id of the hart we are running on
+pub fn hart_id() -> usize {
+    let id;
+    unsafe {
+        core::arch::asm!("mv {}, tp", out(reg) id);
+    }
+    id
+}
+
+fn this_processor() -> &'static UPSafeCell<Processor> {
+    &PROCESSORS[hart_id()]
+}
+
+/// Get the kernel-wide id of the task running on this hart.
+pub fn current_task_id() -> usize {
+    this_processor()
+        .exclusive_access()
+        .current
+        .expect("no task is running on this hart")
+}
+
+/// Record that this hart switches from the idle loop to task `id`.
+pub(super) fn set_current(id: usize) {
+    this_processor().exclusive_access().current = Some(id);
+}
+
+/// Back in the idle loop: forget the task that ran, and return it.
+pub(super) fn take_current() -> Option<usize> {
+    this_processor().exclusive_access().current.take()
+}
+
+/// Record whether this hart is about to wait for a task. Set it before
+/// letting go of the task manager, so that a task made `Ready` after we
+/// looked comes with a [`kick_idle_hart`].
+pub(super) fn set_idle(idle: bool) {
+    let bit = 1 << hart_id();
+    if idle {
+        IDLE_HARTS.fetch_or(bit, Ordering::SeqCst);
+    } else {
+        IDLE_HARTS.fetch_and(!bit, Ordering::SeqCst);
+    }
+}
+
+/// A task became `Ready`: interrupt one idle hart other than us, if there
+/// is one, so that it runs it instead of waiting for its next timer tick.
+pub(super) fn kick_idle_hart() {
+    let others = IDLE_HARTS.load(Ordering::SeqCst) & !(1 << hart_id());
+    if others != 0 {
+        send_ipi(others & others.wrapping_neg());
+    }
+}
+
+/// Where the idle loop of this hart saves its context.
+pub(super) fn idle_task_cx_ptr() -> *mut TaskContext {
+    &mut this_processor().exclusive_access().idle_task_cx as *mut TaskContext
+}
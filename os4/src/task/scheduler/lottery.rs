@@ -0,0 +1,71 @@
+//! Lottery scheduling: every ready task holds `priority` tickets and a random
+//! ticket picks who runs next
+
+use super::Scheduler;
+use alloc::vec::Vec;
+
+/// xorshift64, good enough to draw tickets
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+}
+
+pub struct LotteryScheduler {
+    /// ready tasks with their tickets
+    ready: Vec<(usize, u64)>,
+    rng: Rng,
+}
+
+impl LotteryScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready: Vec::new(),
+            // a fixed seed keeps benchmark runs reproducible
+            rng: Rng(0x2545_f491_4f6c_dd1d),
+        }
+    }
+}
+
+impl Scheduler for LotteryScheduler {
+    fn add(&mut self, id: usize, priority: usize) {
+        self.ready.push((id, priority as u64));
+    }
+    fn remove(&mut self, id: usize) {
+        self.ready.retain(|(i, _)| *i != id);
+    }
+    fn pick_next(&mut self) -> Option<usize> {
+        // tickets can add up to more than u64 with huge priorities
+        let total = self
+            .ready
+            .iter()
+            .fold(0u64, |sum, (_, tickets)| sum.saturating_add(*tickets));
+        if total == 0 {
+            return None;
+        }
+        let mut winner = self.rng.next() % total;
+        let idx = self
+            .ready
+            .iter()
+            .position(|(_, tickets)| {
+                if winner < *tickets {
+                    true
+                } else {
+                    winner -= tickets;
+                    false
+                }
+            })
+            .unwrap_or(self.ready.len() - 1);
+        Some(self.ready.remove(idx).0)
+    }
+    fn tick(&mut self, _id: usize) -> bool {
+        true
+    }
+}
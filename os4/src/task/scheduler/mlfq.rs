@@ -0,0 +1,76 @@
+//! Multi-level feedback queue
+//!
+//! Tasks start in the top queue. One that uses up its whole time slice drops
+//! a level, where slices are longer; one that yields early keeps its level.
+//! Every [`BOOST_INTERVAL`] ticks everybody goes back to the top, so
+//! CPU-bound tasks cannot be starved by a stream of interactive ones.
+
+use super::Scheduler;
+use alloc::collections::{BTreeMap, VecDeque};
+
+/// time slice of each level in timer ticks, top level first
+const SLICES: [usize; 3] = [1, 2, 4];
+const LEVELS: usize = SLICES.len();
+const BOOST_INTERVAL: usize = 64;
+
+#[derive(Copy, Clone, Default)]
+struct TaskLevel {
+    level: usize,
+    /// ticks used of the current slice
+    used: usize,
+}
+
+pub struct MlfqScheduler {
+    queues: [VecDeque<usize>; LEVELS],
+    tasks: BTreeMap<usize, TaskLevel>,
+    ticks_since_boost: usize,
+}
+
+impl MlfqScheduler {
+    pub fn new() -> Self {
+        Self {
+            queues: Default::default(),
+            tasks: BTreeMap::new(),
+            ticks_since_boost: 0,
+        }
+    }
+    fn boost(&mut self) {
+        for state in self.tasks.values_mut() {
+            *state = TaskLevel::default();
+        }
+        let (top, lower) = self.queues.split_at_mut(1);
+        for queue in lower {
+            top[0].extend(queue.drain(..));
+        }
+    }
+}
+
+impl Scheduler for MlfqScheduler {
+    fn add(&mut self, id: usize, _priority: usize) {
+        let level = self.tasks.entry(id).or_default().level;
+        self.queues[level].push_back(id);
+    }
+    fn remove(&mut self, id: usize) {
+        if let Some(state) = self.tasks.remove(&id) {
+            self.queues[state.level].retain(|i| *i != id);
+        }
+    }
+    fn pick_next(&mut self) -> Option<usize> {
+        self.queues.iter_mut().find_map(|queue| queue.pop_front())
+    }
+    fn tick(&mut self, id: usize) -> bool {
+        self.ticks_since_boost += 1;
+        if self.ticks_since_boost >= BOOST_INTERVAL {
+            self.ticks_since_boost = 0;
+            self.boost();
+        }
+        let state = self.tasks.entry(id).or_default();
+        state.used += 1;
+        if state.used < SLICES[state.level] {
+            return false;
+        }
+        state.used = 0;
+        state.level = (state.level + 1).min(LEVELS - 1);
+        true
+    }
+}
@@ -0,0 +1,90 @@
+//! Scheduling policies
+//!
+//! [`TaskManager`](super::TaskManager) only keeps track of what every task is
+//! doing. Which `Ready` task runs next is up to a [`Scheduler`], which knows
+//! tasks only by their task id, so threads of one process are scheduled on
+//! their own:
+//!
+//! - [`Scheduler::add`] when a task becomes `Ready`
+//! - [`Scheduler::pick_next`] takes the task to run out of the policy
+//! - [`Scheduler::tick`] on every timer interrupt while a task is running
+//! - [`Scheduler::remove`] when a task exits, so per-task state can go
+//!
+//! The policy is chosen once at boot: the `sched=` boot parameter if given,
+//! otherwise the `sched-*` cargo feature.
+
+mod lottery;
+mod mlfq;
+mod rr;
+mod stride;
+
+use crate::board::boot_param;
+use alloc::boxed::Box;
+use lottery::LotteryScheduler;
+use mlfq::MlfqScheduler;
+use rr::RoundRobinScheduler;
+use stride::StrideScheduler;
+
+/// a scheduling policy, owned by `TASK_MANAGER` and so `Send`
+pub trait Scheduler: Send {
+    /// `id` became `Ready`, with its current `priority`.
+    fn add(&mut self, id: usize, priority: usize);
+    /// `id` is gone for good, forget about it.
+    fn remove(&mut self, id: usize);
+    /// Take the next task to run out of the ready set.
+    fn pick_next(&mut self) -> Option<usize>;
+    /// A timer tick passed while `id` was running. Returns whether it should
+    /// give up the CPU now.
+    fn tick(&mut self, id: usize) -> bool;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+/// the available policies
+pub enum Policy {
+    RoundRobin,
+    Stride,
+    Mlfq,
+    Lottery,
+}
+
+impl Policy {
+    /// the policy selected by cargo features, stride if there is none
+    fn from_features() -> Self {
+        if cfg!(feature = "sched-rr") {
+            Policy::RoundRobin
+        } else if cfg!(feature = "sched-mlfq") {
+            Policy::Mlfq
+        } else if cfg!(feature = "sched-lottery") {
+            Policy::Lottery
+        } else {
+            Policy::Stride
+        }
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rr" => Some(Policy::RoundRobin),
+            "stride" => Some(Policy::Stride),
+            "mlfq" => Some(Policy::Mlfq),
+            "lottery" => Some(Policy::Lottery),
+            _ => None,
+        }
+    }
+}
+
+/// Create the scheduler selected by the boot parameters or cargo features.
+pub fn new_scheduler() -> Box<dyn Scheduler> {
+    let mut policy = Policy::from_features();
+    if let Some(name) = boot_param("sched") {
+        match Policy::from_name(name.as_str()) {
+            Some(p) => policy = p,
+            None => warn!("[sched] unknown policy \"{}\", using {:?}", name, policy),
+        }
+    }
+    info!("[sched] scheduling policy: {:?}", policy);
+    match policy {
+        Policy::RoundRobin => Box::new(RoundRobinScheduler::new()),
+        Policy::Stride => Box::new(StrideScheduler::new()),
+        Policy::Mlfq => Box::new(MlfqScheduler::new()),
+        Policy::Lottery => Box::new(LotteryScheduler::new()),
+    }
+}
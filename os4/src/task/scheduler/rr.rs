@@ -0,0 +1,31 @@
+//! Round-robin: a FIFO of ready tasks, preempted on every tick
+
+use super::Scheduler;
+use alloc::collections::VecDeque;
+
+pub struct RoundRobinScheduler {
+    ready_queue: VecDeque<usize>,
+}
+
+impl RoundRobinScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for RoundRobinScheduler {
+    fn add(&mut self, id: usize, _priority: usize) {
+        self.ready_queue.push_back(id);
+    }
+    fn remove(&mut self, id: usize) {
+        self.ready_queue.retain(|i| *i != id);
+    }
+    fn pick_next(&mut self) -> Option<usize> {
+        self.ready_queue.pop_front()
+    }
+    fn tick(&mut self, _id: usize) -> bool {
+        true
+    }
+}
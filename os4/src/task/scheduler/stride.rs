@@ -0,0 +1,84 @@
+//! Stride scheduling: the ready task with the smallest pass runs next, and
+//! its pass then advances by `BIG_STRIDE / priority`
+
+use super::Scheduler;
+use crate::config::BIG_STRIDE;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+/// Pass value of a task.
+///
+/// Priorities are at least 2, so a stride is at most `BIG_STRIDE / 2` and the
+/// passes of runnable tasks never drift further apart than that. Comparing
+/// the wrapped difference is therefore still right after a pass overflows.
+///
+/// A priority above `BIG_STRIDE` would make the stride 0, and a task whose
+/// pass never advances would win every pick, so strides are at least 1.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Pass(pub u64);
+
+impl Pass {
+    pub fn step(&mut self, stride: u64) {
+        self.0 = self.0.wrapping_add(stride);
+    }
+}
+
+impl PartialOrd for Pass {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pass {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.0.wrapping_sub(other.0) as i64).cmp(&0)
+    }
+}
+
+pub struct StrideScheduler {
+    /// ready tasks with their stride
+    ready: Vec<(usize, u64)>,
+    /// pass of every task we have seen, ready or not
+    passes: BTreeMap<usize, Pass>,
+    /// pass of the task picked last, where newcomers start
+    current_pass: Pass,
+}
+
+impl StrideScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready: Vec::new(),
+            passes: BTreeMap::new(),
+            current_pass: Pass::default(),
+        }
+    }
+}
+
+impl Scheduler for StrideScheduler {
+    fn add(&mut self, id: usize, priority: usize) {
+        let current_pass = self.current_pass;
+        self.passes.entry(id).or_insert(current_pass);
+        self.ready.push((id, (BIG_STRIDE / priority as u64).max(1)));
+    }
+    fn remove(&mut self, id: usize) {
+        self.ready.retain(|(i, _)| *i != id);
+        self.passes.remove(&id);
+    }
+    fn pick_next(&mut self) -> Option<usize> {
+        let passes = &self.passes;
+        let (idx, _) = self
+            .ready
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, (id, _))| passes[id])?;
+        let (id, stride) = self.ready.remove(idx);
+        let pass = self.passes.get_mut(&id).unwrap();
+        self.current_pass = *pass;
+        pass.step(stride);
+        Some(id)
+    }
+    fn tick(&mut self, _id: usize) -> bool {
+        true
+    }
+}
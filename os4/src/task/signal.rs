@@ -0,0 +1,117 @@
+//! POSIX-like signals
+//!
+//! Signal `n` is bit `1 << n` of [`SignalFlags`]. A thread has its own
+//! pending and blocked sets, while the actions are shared by the whole
+//! process. Pending signals are looked at right before a thread returns to
+//! user mode: a handler runs on the interrupted thread's stack with the
+//! signal number in `a0`, and returns through the `sigreturn` trampoline,
+//! which puts back the trap context saved on delivery.
+//!
+//! There is no job control, so `SIGSTOP` and `SIGCONT` are ignored.
+
+use super::ExitReason;
+
+/// the largest signal number
+pub const MAX_SIG: usize = 31;
+
+/// `SignalAction::handler` for the default action
+pub const SIG_DFL: usize = 0;
+/// `SignalAction::handler` to ignore the signal
+pub const SIG_IGN: usize = 1;
+
+bitflags! {
+    /// a set of signals, with the numbers Linux uses
+    pub struct SignalFlags: u32 {
+        const SIGHUP = 1 << 1;
+        const SIGINT = 1 << 2;
+        const SIGQUIT = 1 << 3;
+        const SIGILL = 1 << 4;
+        const SIGTRAP = 1 << 5;
+        const SIGABRT = 1 << 6;
+        const SIGBUS = 1 << 7;
+        const SIGFPE = 1 << 8;
+        const SIGKILL = 1 << 9;
+        const SIGUSR1 = 1 << 10;
+        const SIGSEGV = 1 << 11;
+        const SIGUSR2 = 1 << 12;
+        const SIGPIPE = 1 << 13;
+        const SIGALRM = 1 << 14;
+        const SIGTERM = 1 << 15;
+        const SIGSTKFLT = 1 << 16;
+        const SIGCHLD = 1 << 17;
+        const SIGCONT = 1 << 18;
+        const SIGSTOP = 1 << 19;
+        const SIGTSTP = 1 << 20;
+        const SIGTTIN = 1 << 21;
+        const SIGTTOU = 1 << 22;
+        const SIGURG = 1 << 23;
+        const SIGXCPU = 1 << 24;
+        const SIGXFSZ = 1 << 25;
+        const SIGVTALRM = 1 << 26;
+        const SIGPROF = 1 << 27;
+        const SIGWINCH = 1 << 28;
+        const SIGIO = 1 << 29;
+        const SIGPWR = 1 << 30;
+        const SIGSYS = 1 << 31;
+        /// can be neither caught, blocked nor ignored
+        const UNCATCHABLE = Self::SIGKILL.bits | Self::SIGSTOP.bits;
+        /// ignored unless caught
+        const DEFAULT_IGNORED = Self::SIGCHLD.bits
+            | Self::SIGCONT.bits
+            | Self::SIGSTOP.bits
+            | Self::SIGURG.bits
+            | Self::SIGWINCH.bits;
+        /// raised by the kernel for a bad access or instruction
+        const FAULTS = Self::SIGILL.bits
+            | Self::SIGTRAP.bits
+            | Self::SIGBUS.bits
+            | Self::SIGFPE.bits
+            | Self::SIGSEGV.bits;
+    }
+}
+
+impl SignalFlags {
+    /// the set holding just signal `signum`, `None` if there is no such signal
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if (1..=MAX_SIG).contains(&signum) {
+            Self::from_bits(1 << signum)
+        } else {
+            None
+        }
+    }
+    /// the smallest signal number in the set
+    pub fn first_signum(&self) -> Option<usize> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.bits.trailing_zeros() as usize)
+        }
+    }
+}
+
+/// what to do with a signal, as passed to `sigaction`
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SignalAction {
+    /// [`SIG_DFL`], [`SIG_IGN`] or the address of the handler
+    pub handler: usize,
+    /// signals blocked while the handler runs, besides the signal itself
+    pub mask: SignalFlags,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+/// Why a process killed by signal `signum` is considered to have exited.
+pub fn exit_reason_of(signum: usize) -> ExitReason {
+    match SignalFlags::from_signum(signum) {
+        Some(signal) if SignalFlags::FAULTS.contains(signal) => ExitReason::Fault,
+        _ => ExitReason::Killed,
+    }
+}
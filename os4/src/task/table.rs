@@ -0,0 +1,59 @@
+//! The table of all tasks, indexed by task id
+//!
+//! Every thread has a kernel-wide task id. Ids of reaped tasks are handed
+//! out again by the allocator in [`super::id`], so slots are reused and the
+//! table only grows to the largest number of tasks alive at the same time.
+
+use super::TaskControlBlock;
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut};
+
+pub struct TaskTable {
+    slots: Vec<Option<TaskControlBlock>>,
+}
+
+impl TaskTable {
+    pub fn new() -> Self {
+        Self { slots: Vec::new() }
+    }
+    /// Put `task` in the slot of its id and return the id.
+    pub fn insert(&mut self, task: TaskControlBlock) -> usize {
+        let id = task.id();
+        if id >= self.slots.len() {
+            self.slots.resize_with(id + 1, || None);
+        }
+        assert!(self.slots[id].is_none(), "task id {} is in use", id);
+        self.slots[id] = Some(task);
+        id
+    }
+    /// Take task `id` out of the table.
+    pub fn remove(&mut self, id: usize) -> Option<TaskControlBlock> {
+        self.slots.get_mut(id)?.take()
+    }
+    pub fn get(&self, id: usize) -> Option<&TaskControlBlock> {
+        self.slots.get(id)?.as_ref()
+    }
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut TaskControlBlock> {
+        self.slots.get_mut(id)?.as_mut()
+    }
+    /// all tasks, in order of id
+    pub fn iter(&self) -> impl Iterator<Item = &TaskControlBlock> {
+        self.slots.iter().flatten()
+    }
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut TaskControlBlock> {
+        self.slots.iter_mut().flatten()
+    }
+}
+
+impl Index<usize> for TaskTable {
+    type Output = TaskControlBlock;
+    fn index(&self, id: usize) -> &TaskControlBlock {
+        self.get(id).expect("no task with this id")
+    }
+}
+
+impl IndexMut<usize> for TaskTable {
+    fn index_mut(&mut self, id: usize) -> &mut TaskControlBlock {
+        self.get_mut(id).expect("no task with this id")
+    }
+}
@@ -1,93 +1,195 @@
 //! Types related to task management
-use super::TaskContext;
-use crate::config::{kernel_stack_position, TRAP_CONTEXT, MAX_SYSCALL_NUM};
-use crate::mm::{MapPermission, MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
-use crate::trap::{trap_handler, TrapContext};
+use super::{kstack_alloc, task_id_alloc, KernelStack, SignalFlags, TaskContext, TaskIdHandle};
+use crate::board::boot_param;
+use crate::config::{DEFAULT_CPU_LIMIT_MS, DEFAULT_PRIORITY, MAX_SYSCALL_NUM};
+use crate::mm::PhysPageNum;
+use crate::trap::TrapContext;
+use lazy_static::*;
 
+lazy_static! {
+    /// CPU time budget of a new task in microseconds, from `cpulimit=<ms>`
+    /// on the kernel command line or [`DEFAULT_CPU_LIMIT_MS`]
+    static ref DEFAULT_CPU_LIMIT_US: Option<usize> = {
+        let mut limit_ms = DEFAULT_CPU_LIMIT_MS;
+        if let Some(value) = boot_param("cpulimit") {
+            match value.parse::<usize>() {
+                Ok(ms) if ms.checked_mul(1000).is_some() => limit_ms = ms,
+                _ => warn!("[kernel] bad cpulimit \"{}\" ignored", value),
+            }
+        }
+        match limit_ms {
+            0 => None,
+            ms => Some(ms * 1000),
+        }
+    };
+}
+
+/// Accounting of a task, all times in microseconds.
+///
+/// CPU time is charged at the boundaries: to user mode when a trap comes in,
+/// to kernel mode when we return to user or switch away.
 #[derive(Copy, Clone, Debug)]
 pub struct TaskStatsInfo {
-    pub first_run_time: usize,
+    /// when the task was first scheduled, `None` if it has not run yet
+    pub first_run_time: Option<usize>,
+    /// when the task was last switched in
+    pub last_run_time: usize,
+    pub user_time: usize,
+    pub kernel_time: usize,
+    /// switches away because the task yielded, slept or blocked
+    pub voluntary_switches: usize,
+    /// switches away because its time slice ran out
+    pub involuntary_switches: usize,
+    /// the last time CPU time was charged
+    stamp: usize,
     pub system_call_record: [u32; MAX_SYSCALL_NUM],
 }
 
 impl Default for TaskStatsInfo {
     fn default() -> Self {
         TaskStatsInfo { 
-            // task_status: TaskStatus,        
-            first_run_time: 0, 
+            first_run_time: None,
+            last_run_time: 0,
+            user_time: 0,
+            kernel_time: 0,
+            voluntary_switches: 0,
+            involuntary_switches: 0,
+            stamp: 0,
             system_call_record: [0; MAX_SYSCALL_NUM] 
         }
     }
 }
 
 impl TaskStatsInfo {
-    pub fn get_info(&self) -> ([u32; MAX_SYSCALL_NUM], usize) {
-        (self.system_call_record, self.first_run_time)
+    /// syscall counts and wall time since the first run
+    pub fn get_info(&self, now: usize) -> ([u32; MAX_SYSCALL_NUM], usize) {
+        (self.system_call_record, self.wall_time(now))
+    }
+    /// time since the task was first scheduled
+    pub fn wall_time(&self, now: usize) -> usize {
+        self.first_run_time.map_or(0, |first| now - first)
+    }
+    /// CPU time charged so far, in user and kernel mode
+    pub fn cpu_time(&self) -> usize {
+        self.user_time + self.kernel_time
+    }
+    /// The task is switched in at `now`, it runs in kernel mode from here.
+    pub fn switch_in(&mut self, now: usize) {
+        self.first_run_time.get_or_insert(now);
+        self.last_run_time = now;
+        self.stamp = now;
+    }
+    /// Charge the time since the last boundary to user mode.
+    pub fn charge_user(&mut self, now: usize) {
+        self.user_time += now - self.stamp;
+        self.stamp = now;
+    }
+    /// Charge the time since the last boundary to kernel mode.
+    pub fn charge_kernel(&mut self, now: usize) {
+        self.kernel_time += now - self.stamp;
+        self.stamp = now;
     }
 }
 
-/// task control block structure
+/// task control block structure: one thread of a process
 pub struct TaskControlBlock {
+    /// kernel-wide id, what the scheduler and the task table know us by
+    pub id: TaskIdHandle,
+    /// the process we belong to
+    pub pid: usize,
+    /// thread id inside the process, 0 for the main thread
+    pub tid: usize,
     pub task_status: TaskStatus,
+    /// the hart we run on, from when it switches to us until it is back in
+    /// its idle loop. Nobody else may switch to us meanwhile
+    pub on_cpu: Option<usize>,
+    /// a wakeup came while we were still `Running`, on our way to block.
+    /// The block does not happen then
+    pub wakeup_pending: bool,
     pub task_cx: TaskContext,
     pub stats: TaskStatsInfo,
-    pub memory_set: MemorySet,
     pub trap_cx_ppn: PhysPageNum,
-    pub base_size: usize,
+    /// `None` once the task has exited and its stack was given back
+    pub kernel_stack: Option<KernelStack>,
+    /// collected by `waittid`
+    pub exit_code: i32,
+    /// handed to the scheduler, what it means depends on the policy
+    pub priority: usize,
+    /// CPU time in microseconds the task may use before it is terminated,
+    /// `None` for no limit
+    pub cpu_limit_us: Option<usize>,
+    /// signals sent to us and not delivered yet
+    pub signals: SignalFlags,
+    /// signals we do not want delivered for now
+    pub signal_mask: SignalFlags,
+    /// the signal whose handler is running, at most one at a time
+    pub handling_sig: Option<usize>,
+    /// what the handler interrupted, put back by `sigreturn`, together with
+    /// the mask at that time
+    pub trap_ctx_backup: Option<(TrapContext, SignalFlags)>,
 }
 
 impl TaskControlBlock {
+    /// A thread `tid` of process `pid` whose `TrapContext` is in the frame
+    /// `trap_cx_ppn`. The caller fills in the trap context.
+    pub fn new(pid: usize, tid: usize, trap_cx_ppn: PhysPageNum) -> Self {
+        // map a kernel-stack in kernel space
+        let kernel_stack = kstack_alloc();
+        let kernel_stack_top = kernel_stack.get_top();
+        Self {
+            id: task_id_alloc(),
+            pid,
+            tid,
+            task_status: TaskStatus::Ready,
+            on_cpu: None,
+            wakeup_pending: false,
+            task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+            stats: TaskStatsInfo::default(),
+            trap_cx_ppn,
+            kernel_stack: Some(kernel_stack),
+            exit_code: 0,
+            priority: DEFAULT_PRIORITY,
+            cpu_limit_us: *DEFAULT_CPU_LIMIT_US,
+            signals: SignalFlags::empty(),
+            signal_mask: SignalFlags::empty(),
+            handling_sig: None,
+            trap_ctx_backup: None,
+        }
+    }
     pub fn get_trap_cx(&self) -> &'static mut TrapContext {
         self.trap_cx_ppn.get_mut()
     }
-    pub fn get_user_token(&self) -> usize {
-        self.memory_set.token()
+    pub fn id(&self) -> usize {
+        self.id.0
     }
-    pub fn new(elf_data: &[u8], app_id: usize) -> Self {
-        // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
-        let trap_cx_ppn = memory_set
-            .translate(VirtAddr::from(TRAP_CONTEXT).into())
-            .unwrap()
-            .ppn();
-        let task_status = TaskStatus::Ready;
-        // map a kernel-stack in kernel space
-        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(app_id);
-        KERNEL_SPACE.lock().insert_framed_area(
-            kernel_stack_bottom.into(),
-            kernel_stack_top.into(),
-            MapPermission::R | MapPermission::W,
-        );
-        let stats = TaskStatsInfo { 
-            first_run_time: 0, 
-            system_call_record: [0 ; MAX_SYSCALL_NUM] 
-        };
-        let task_control_block = Self {
-            task_status,
-            task_cx: TaskContext::goto_trap_return(kernel_stack_top),
-            stats,
-            memory_set,
-            trap_cx_ppn,
-            base_size: user_sp,
-        };
-        // prepare TrapContext in user space
-        let trap_cx = task_control_block.get_trap_cx();
-        *trap_cx = TrapContext::app_init_context(
-            entry_point,
-            user_sp,
-            KERNEL_SPACE.lock().token(),
-            kernel_stack_top,
-            trap_handler as usize,
-        );
-        task_control_block
+    pub fn kernel_stack_top(&self) -> usize {
+        self.kernel_stack.as_ref().unwrap().get_top()
     }
 }
 
 #[derive(Copy, Clone, PartialEq)]
-/// task status: UnInit, Ready, Running, Exited
+/// task status: UnInit, Ready, Running, Exited, Blocked
+///
+/// An `Exited` thread stays around until another thread of its process
+/// waits for it, or the whole process exits.
+/// A `Blocked` task is not known to the scheduler until someone wakes it up.
 pub enum TaskStatus {
     UnInit,
     Ready,
     Running,
     Exited,
+    Blocked,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+/// why a process exited
+pub enum ExitReason {
+    /// it called `sys_exit`
+    Normal,
+    /// the kernel killed it for a bad access or instruction
+    Fault,
+    /// a signal it did not catch killed it
+    Killed,
+    /// one of its threads used up its CPU time budget
+    CpuLimit,
 }
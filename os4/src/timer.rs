@@ -1,10 +1,25 @@
 //! RISC-V timer-related functionality
+//!
+//! Besides the periodic scheduling tick, tasks blocked in `sys_sleep` wait in
+//! a min-heap of deadlines. The timer is always programmed for whichever
+//! comes first, the next tick or the earliest deadline.
+//!
+//! Every hart has a timer of its own and ticks on its own schedule, the
+//! sleepers are shared: whichever hart sees a deadline pass wakes them up.
 
-use crate::config::CLOCK_FREQ;
+use crate::board::clock_freq;
+use crate::config::MAX_HARTS;
 use crate::sbi::set_timer;
+use crate::sync::SpinLock;
+use crate::task::{hart_id, wakeup_task};
+use alloc::collections::BinaryHeap;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use lazy_static::*;
 use riscv::register::time;
 
 const TICKS_PER_SEC: usize = 100;
+const MSEC_PER_SEC: usize = 1000;
 const MICRO_PER_SEC: usize = 1_000_000;
 
 /// read the `mtime` register
@@ -12,12 +27,120 @@ pub fn get_time() -> usize {
     time::read()
 }
 
+/// get current time in milliseconds
+pub fn get_time_ms() -> usize {
+    time::read() * MSEC_PER_SEC / clock_freq()
+}
+
 /// get current time in microseconds
 pub fn get_time_us() -> usize {
-    time::read() / (CLOCK_FREQ / MICRO_PER_SEC)
+    time::read() * MICRO_PER_SEC / clock_freq()
+}
+
+/// a task sleeping until `expire_ms`
+pub struct TimerCondVar {
+    pub expire_ms: usize,
+    /// task id of the sleeper
+    pub id: usize,
+}
+
+impl PartialEq for TimerCondVar {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire_ms == other.expire_ms
+    }
 }
 
-/// set the next timer interrupt
+impl Eq for TimerCondVar {}
+
+impl PartialOrd for TimerCondVar {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerCondVar {
+    /// reversed, so that `BinaryHeap` pops the earliest deadline first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.expire_ms.cmp(&self.expire_ms)
+    }
+}
+
+struct TimerState {
+    /// `mtime` of the next scheduling tick of each hart
+    next_tick: [usize; MAX_HARTS],
+    sleepers: BinaryHeap<TimerCondVar>,
+}
+
+lazy_static! {
+    static ref TIMER: SpinLock<TimerState> = SpinLock::new(TimerState {
+        next_tick: [0; MAX_HARTS],
+        sleepers: BinaryHeap::new(),
+    });
+}
+
+/// Set the next timer interrupt of this hart, at its next tick or the
+/// earliest deadline.
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    let hart = hart_id();
+    let mut timer = TIMER.lock();
+    let now = get_time();
+    if timer.next_tick[hart] <= now {
+        timer.next_tick[hart] = now + clock_freq() / TICKS_PER_SEC;
+    }
+    let mut next = timer.next_tick[hart];
+    if let Some(sleeper) = timer.sleepers.peek() {
+        let expire = sleeper.expire_ms.saturating_mul(clock_freq()) / MSEC_PER_SEC;
+        next = next.min(expire);
+    }
+    set_timer(next);
+}
+
+/// Put task `id` to sleep until `expire_ms`. The caller blocks it.
+///
+/// Only this hart's timer is moved up to the deadline, the others may see
+/// it up to a tick late.
+pub fn add_timer(expire_ms: usize, id: usize) {
+    TIMER
+        .lock()
+        .sleepers
+        .push(TimerCondVar { expire_ms, id });
+    set_next_trigger();
+}
+
+/// Forget the deadline of task `id`, which exited while asleep. Its id may
+/// be handed out again before the deadline.
+pub fn remove_timer(id: usize) {
+    let mut timer = TIMER.lock();
+    let sleepers = core::mem::take(&mut timer.sleepers);
+    timer.sleepers = sleepers
+        .into_iter()
+        .filter(|sleeper| sleeper.id != id)
+        .collect();
+}
+
+/// Wake up the tasks whose deadline has passed.
+fn wake_expired_sleepers() {
+    let now_ms = get_time_ms();
+    let mut expired = Vec::new();
+    let mut timer = TIMER.lock();
+    while let Some(sleeper) = timer.sleepers.peek() {
+        if sleeper.expire_ms > now_ms {
+            break;
+        }
+        expired.push(timer.sleepers.pop().unwrap().id);
+    }
+    drop(timer);
+    for id in expired {
+        wakeup_task(id);
+    }
+}
+
+/// Handle a timer interrupt: wake up sleepers and program the next one.
+/// Returns whether a scheduling tick of this hart has passed, rather than
+/// just a deadline.
+pub fn check_timer() -> bool {
+    wake_expired_sleepers();
+    let tick = TIMER.lock().next_tick[hart_id()] <= get_time();
+    set_next_trigger();
+    tick
 }
@@ -3,6 +3,7 @@
 use riscv::register::sstatus::{self, Sstatus, SPP};
 
 #[repr(C)]
+#[derive(Clone, Copy)]
 /// trap context structure containing sstatus, sepc and registers
 pub struct TrapContext {
     pub x: [usize; 32],
@@ -11,6 +12,7 @@ pub struct TrapContext {
     pub kernel_satp: usize,         // the token of kernel address space 
     pub kernel_sp: usize,           // the VPN of kernel stack top
     pub trap_handler: usize,        // the VPN of the entry of trap_handler
+    pub kernel_hartid: usize,       // the hart we last left for user mode, put back in tp on a trap
 }
 
 impl TrapContext {
@@ -33,6 +35,7 @@ impl TrapContext {
             kernel_satp,
             kernel_sp,
             trap_handler,
+            kernel_hartid: 0,
         };
         cx.set_sp(sp);
         cx
@@ -0,0 +1,176 @@
+//! Crash reports for user tasks killed by a fault
+//!
+//! The report is one block of console lines, each starting with `[core]`
+//! and made of a keyword followed by fields, numbers in hex:
+//!
+//! ```text
+//! [core] begin pid=2 tid=2 hart=0
+//! [core] cause scause=0xf name=store-page-fault stval=0x0
+//! [core] reg x1 ra 0x000000000001008a
+//! [core] reg pc 0x0000000000010086
+//! [core] insn pc=0x10086 bytes=23300500
+//! [core] area start=0x10000 end=0x12000 perm=r-xu
+//! [core] frame 0 pc=0x10086 fp=0x1f000
+//! [core] end
+//! ```
+//!
+//! There is a `reg` line for each of `x1`..`x31` and the `pc`, and one
+//! `area` line for each area of the task's `MemorySet`. `insn` holds the
+//! bytes at the `pc` in memory order, `bytes=unmapped` if they cannot be
+//! read. `frame 0` is the faulting `pc`, each further frame is a return
+//! address found by following the saved frame pointers up the user stack,
+//! so it only goes past frame 0 if the app was built with frame pointers.
+
+use super::TrapContext;
+use crate::mm::{MapPermission, MemorySet};
+use alloc::string::String;
+use core::fmt::Write;
+
+/// the most frames to follow, in case the saved frame pointers are garbage
+const MAX_FRAMES: usize = 32;
+
+/// ABI names of `x0`..`x31`
+const REG_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+/// Name of the exception `scause` stands for, as in the privileged spec.
+/// Only the causes a user task can fault with reach us.
+fn cause_name(scause: usize) -> &'static str {
+    match scause {
+        0 => "instruction-misaligned",
+        1 => "instruction-access-fault",
+        2 => "illegal-instruction",
+        3 => "breakpoint",
+        4 => "load-misaligned",
+        5 => "load-access-fault",
+        6 => "store-misaligned",
+        7 => "store-access-fault",
+        12 => "instruction-page-fault",
+        13 => "load-page-fault",
+        15 => "store-page-fault",
+        _ => "unknown",
+    }
+}
+
+/// `rwxu` with `-` for each permission missing
+fn perm_string(perm: MapPermission) -> String {
+    [
+        (MapPermission::R, 'r'),
+        (MapPermission::W, 'w'),
+        (MapPermission::X, 'x'),
+        (MapPermission::U, 'u'),
+    ]
+    .iter()
+    .map(|&(flag, c)| if perm.contains(flag) { c } else { '-' })
+    .collect()
+}
+
+/// Read a `usize` of user memory, `None` if it is not mapped.
+fn read_usize(memory_set: &MemorySet, va: usize) -> Option<usize> {
+    let mut bytes = [0u8; core::mem::size_of::<usize>()];
+    if memory_set.read_user(va, &mut bytes) {
+        Some(usize::from_le_bytes(bytes))
+    } else {
+        None
+    }
+}
+
+/// Build the report for task `tid` of process `pid`, which trapped on
+/// `hart` with `scause` and `stval` in the state `cx`.
+pub fn core_dump(
+    pid: usize,
+    tid: usize,
+    hart: usize,
+    scause: usize,
+    stval: usize,
+    cx: &TrapContext,
+    memory_set: &MemorySet,
+) -> String {
+    let mut out = String::new();
+    // writing to a `String` cannot fail
+    let _ = write_core_dump(&mut out, pid, tid, hart, scause, stval, cx, memory_set);
+    out
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_core_dump(
+    out: &mut String,
+    pid: usize,
+    tid: usize,
+    hart: usize,
+    scause: usize,
+    stval: usize,
+    cx: &TrapContext,
+    memory_set: &MemorySet,
+) -> core::fmt::Result {
+    writeln!(out, "[core] begin pid={} tid={} hart={}", pid, tid, hart)?;
+    writeln!(
+        out,
+        "[core] cause scause={:#x} name={} stval={:#x}",
+        scause,
+        cause_name(scause),
+        stval
+    )?;
+    for (i, name) in REG_NAMES.iter().enumerate().skip(1) {
+        writeln!(out, "[core] reg x{} {} {:#018x}", i, name, cx.x[i])?;
+    }
+    writeln!(out, "[core] reg pc {:#018x}", cx.sepc)?;
+
+    // an instruction is compressed unless its low two bits are both set
+    let mut insn = [0u8; 4];
+    let len = if memory_set.read_user(cx.sepc, &mut insn[..2]) && insn[0] & 0b11 != 0b11 {
+        2
+    } else if memory_set.read_user(cx.sepc, &mut insn) {
+        4
+    } else {
+        0
+    };
+    write!(out, "[core] insn pc={:#x} bytes=", cx.sepc)?;
+    if len == 0 {
+        out.push_str("unmapped");
+    }
+    for byte in &insn[..len] {
+        write!(out, "{:02x}", byte)?;
+    }
+    out.push('\n');
+
+    for (start, end, perm) in memory_set.areas() {
+        writeln!(
+            out,
+            "[core] area start={:#x} end={:#x} perm={}",
+            usize::from(start),
+            usize::from(end),
+            perm_string(perm)
+        )?;
+    }
+
+    // with frame pointers, the return address is saved right below where
+    // `s0` points and the caller's `s0` below it
+    let mut fp = cx.x[8];
+    writeln!(out, "[core] frame 0 pc={:#x} fp={:#x}", cx.sepc, fp)?;
+    for i in 1..MAX_FRAMES {
+        if fp % core::mem::size_of::<usize>() != 0 || fp < 16 {
+            break;
+        }
+        let (ra, caller_fp) = match (
+            read_usize(memory_set, fp - 8),
+            read_usize(memory_set, fp - 16),
+        ) {
+            (Some(ra), Some(caller_fp)) => (ra, caller_fp),
+            _ => break,
+        };
+        if ra == 0 {
+            break;
+        }
+        writeln!(out, "[core] frame {} pc={:#x} fp={:#x}", i, ra, caller_fp)?;
+        // the stack grows down, so callers' frames are above ours
+        if caller_fp <= fp {
+            break;
+        }
+        fp = caller_fp;
+    }
+    writeln!(out, "[core] end")
+}
@@ -12,17 +12,22 @@
 //! was. For example, timer interrupts trigger task preemption, and syscalls go
 //! to [`syscall()`].
 mod context;
+mod coredump;
 
-use crate::config::{TRAMPOLINE, TRAP_CONTEXT};
+use crate::config::TRAMPOLINE;
 use crate::syscall::syscall;
 use crate::task::{
-    current_trap_cx, current_user_token, exit_current_and_run_next, suspend_current_and_run_next,
+    account_kernel_time, account_user_time, current_trap_cx, current_trap_cx_user_va,
+    current_user_token, enforce_cpu_limit, handle_signals, hart_id, preempt_current_and_run_next,
+    raise_fault_signal, scheduler_tick, SignalFlags,
 };
-use crate::timer::set_next_trigger;
+use crate::sync::interrupt_depth;
+use crate::timer::check_timer;
+use riscv::asm::wfi;
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
-    sie, stval, stvec,
+    sepc, sie, sip, sstatus, stval, stvec,
 };
 
 core::arch::global_asm!(include_str!("trap.S"));
@@ -32,8 +37,11 @@ pub fn init() {
 }
 
 fn set_kernel_trap_entry() {
+    extern "C" {
+        fn __kernel_trap();
+    }
     unsafe {
-        stvec::write(trap_from_kernel as usize, TrapMode::Direct);
+        stvec::write(__kernel_trap as usize, TrapMode::Direct);
     }
 }
 
@@ -49,30 +57,78 @@ pub fn enable_timer_interrupt() {
     }
 }
 
+/// let other harts wake us up with an IPI when there is a task to run
+pub fn enable_soft_interrupt() {
+    unsafe {
+        sie::set_ssoft();
+    }
+}
+
+/// Sleep until an interrupt comes, and handle it.
+///
+/// `wfi` runs with interrupts still disabled, so that one coming in just
+/// before is left pending and wakes us up right away rather than being taken
+/// early. They are enabled afterwards for just long enough to take it.
+pub fn wait_for_interrupt() {
+    // a handler could find a borrow or a lock of ours taken
+    assert_eq!(interrupt_depth(), 0, "waiting for an interrupt inside push_off");
+    unsafe {
+        wfi();
+        sstatus::set_sie();
+        sstatus::clear_sie();
+    }
+}
+
+/// The signal a fault of a user task with exception code `code` raises,
+/// `None` for causes that cannot come from U-mode. Decoded from the code
+/// because the `riscv` crate has no variant for every cause.
+fn fault_signal(code: usize) -> Option<SignalFlags> {
+    match code {
+        // instruction, load and store address misaligned
+        0 | 4 | 6 => Some(SignalFlags::SIGBUS),
+        // access faults and page faults
+        1 | 5 | 7 | 12 | 13 | 15 => Some(SignalFlags::SIGSEGV),
+        2 => Some(SignalFlags::SIGILL),
+        3 => Some(SignalFlags::SIGTRAP),
+        _ => None,
+    }
+}
+
 #[no_mangle]
 pub fn trap_handler() -> ! {
     set_kernel_trap_entry();
+    account_user_time();
     let cx = current_trap_cx();
     let scause = scause::read();
     let stval = stval::read();
     match scause.cause() {
         Trap::Exception(Exception::UserEnvCall) => {
             cx.sepc += 4;
-            cx.x[10] = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
-        }
-        Trap::Exception(Exception::StoreFault)
-        | Trap::Exception(Exception::StorePageFault)
-        | Trap::Exception(Exception::LoadPageFault) => {
-            error!("[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}, core dumped.", stval, cx.sepc);
-            exit_current_and_run_next();
+            let result = syscall(
+                cx.x[17],
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            );
+            // `exec` builds a new trap context, so look it up again
+            current_trap_cx().x[10] = result as usize;
         }
-        Trap::Exception(Exception::IllegalInstruction) => {
-            error!("[kernel] IllegalInstruction in application, core dumped.");
-            exit_current_and_run_next();
+        Trap::Exception(_) if fault_signal(scause.code()).is_some() => {
+            let signal = fault_signal(scause.code()).unwrap();
+            raise_fault_signal(signal, scause.bits(), stval);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            set_next_trigger();
-            suspend_current_and_run_next();
+            // a sleeper's deadline may have fired the interrupt before the tick
+            if check_timer() {
+                enforce_cpu_limit();
+                if scheduler_tick() {
+                    preempt_current_and_run_next();
+                }
+            }
+        }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            // a hart kicked us while we were idle, and we are busy by now
+            unsafe {
+                sip::clear_ssoft();
+            }
         }
         _ => {
             panic!(
@@ -82,13 +138,17 @@ pub fn trap_handler() -> ! {
             );
         }
     }
+    handle_signals();
     trap_return();
 }
 
 #[no_mangle]
 pub fn trap_return() -> ! {
     set_user_trap_entry();
-    let trap_cx_ptr = TRAP_CONTEXT;
+    account_kernel_time();
+    // we may come back on another hart than the one we trapped in on
+    current_trap_cx().kernel_hartid = hart_id();
+    let trap_cx_ptr = current_trap_cx_user_va();
     let user_satp = current_user_token();
     extern "C" {
         fn __alltraps();
@@ -108,8 +168,28 @@ pub fn trap_return() -> ! {
 }
 
 #[no_mangle]
-pub fn trap_from_kernel() -> ! {
-    panic!("a trap from kernel!");
+/// Handle an interrupt taken while the idle loop waits. A timer interrupt
+/// may wake sleepers up, an IPI just tells us there is a task to run; the
+/// idle loop looks for one either way. Anything else is a kernel bug.
+pub fn trap_from_kernel() {
+    let scause = scause::read();
+    match scause.cause() {
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            check_timer();
+        }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => unsafe {
+            sip::clear_ssoft();
+        },
+        _ => {
+            panic!(
+                "a trap from kernel: {:?}, stval = {:#x}, sepc = {:#x}",
+                scause.cause(),
+                stval::read(),
+                sepc::read()
+            );
+        }
+    }
 }
 
 pub use context::TrapContext;
+pub use coredump::core_dump;
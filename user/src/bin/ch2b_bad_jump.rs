@@ -0,0 +1,14 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+/// 跳转到未映射的地址 0，应触发 InstructionPageFault 并被内核杀死，
+/// 内核会打印 `[core]` 崩溃报告
+
+#[no_mangle]
+pub fn main() -> isize {
+    let entry: fn() = unsafe { core::mem::transmute(0usize) };
+    entry();
+    panic!("FAIL: T.T\n");
+}
@@ -0,0 +1,26 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::mmap;
+
+/// 正确输出：
+/// mmap empty pass.
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096;
+    let prot: usize = 3;
+    // an empty mapping succeeds and takes no room
+    assert_eq!(mmap(start, 0, prot), 0);
+    assert_eq!(mmap(start, len, prot), 0);
+    assert_eq!(mmap(start, 0, prot), 0);
+    // but a bad start or prot is still refused
+    assert_eq!(mmap(start + 1, 0, prot), -1);
+    assert_eq!(mmap(start, 0, 0), -1);
+    println!("mmap empty pass.");
+    0
+}
@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, sys_pipe, sys_waitpid, sys_write, waitpid, yield_};
+
+/// 正确输出：
+/// bad pointers pass.
+
+/// an address that is never mapped in a user address space
+const BAD_ADDR: usize = 0x10;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let bad_buf = unsafe { core::slice::from_raw_parts(BAD_ADDR as *const u8, 4) };
+    assert_eq!(sys_write(1, bad_buf), -1);
+    let bad_fds = unsafe { core::slice::from_raw_parts_mut(BAD_ADDR as *mut usize, 2) };
+    assert_eq!(sys_pipe(bad_fds), -1);
+    let pid = fork();
+    if pid == 0 {
+        exit(7);
+    }
+    // a bad pointer must not cost us the exit code of the child
+    loop {
+        match sys_waitpid(pid, BAD_ADDR as *mut i32) {
+            -2 => {
+                yield_();
+            }
+            result => {
+                assert_eq!(result, -1);
+                break;
+            }
+        }
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 7);
+    println!("bad pointers pass.");
+    0
+}
@@ -0,0 +1,26 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, set_priority, waitpid};
+
+/// 正确输出：
+/// huge priority pass.
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        exit(3);
+    }
+    // waitpid yields while the child runs; with a pass that never advances
+    // we would be picked again every time and the child would never exit
+    assert_eq!(set_priority(isize::MAX), isize::MAX);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 3);
+    println!("huge priority pass.");
+    0
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{fork, set_cpu_limit, waitpid, SIGXCPU};
+
+/// 正确输出：
+/// cpu limit pass.
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(set_cpu_limit(-1), -1);
+    assert_eq!(set_cpu_limit(isize::MAX), -1);
+    let old = set_cpu_limit(0);
+    assert!(old >= 0);
+    assert_eq!(set_cpu_limit(5000), 0);
+    assert_eq!(set_cpu_limit(old), 5000);
+    let pid = fork();
+    if pid == 0 {
+        set_cpu_limit(20);
+        #[allow(clippy::empty_loop)]
+        loop {}
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -SIGXCPU);
+    println!("cpu limit pass.");
+    0
+}
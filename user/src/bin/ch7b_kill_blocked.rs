@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{fork, kill, sleep, sleep_blocking, waitpid, SIGKILL};
+
+/// 正确输出：
+/// kill blocked pass.
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        // far longer than the test may take
+        sleep_blocking(1_000_000);
+        panic!("FAIL: the sleeper was not killed");
+    }
+    // let the child block first
+    sleep(50);
+    assert_eq!(kill(pid as usize, SIGKILL), 0);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -SIGKILL);
+    println!("kill blocked pass.");
+    0
+}
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, getpid, kill, sigaction, sigprocmask, wait, yield_};
+use user_lib::{SignalAction, SignalFlags, SIGKILL, SIGUSR1};
+
+static mut CAUGHT: usize = 0;
+
+extern "C" fn on_usr1(signum: usize) {
+    unsafe {
+        CAUGHT = signum;
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let action = SignalAction {
+        handler: on_usr1 as usize,
+        mask: SignalFlags::empty(),
+    };
+    assert_eq!(sigaction(SIGUSR1, Some(&action), None), 0);
+    assert_eq!(sigaction(SIGKILL, Some(&action), None), -1);
+
+    // the handler runs on the way back from kill
+    assert_eq!(kill(getpid() as usize, SIGUSR1), 0);
+    assert_eq!(unsafe { CAUGHT }, SIGUSR1 as usize);
+    println!("signal handler test passed!");
+
+    // blocked signals wait until they are unblocked
+    unsafe {
+        CAUGHT = 0;
+    }
+    assert_eq!(sigprocmask(SignalFlags::SIGUSR1.bits()), 0);
+    kill(getpid() as usize, SIGUSR1);
+    assert_eq!(unsafe { CAUGHT }, 0);
+    sigprocmask(0);
+    assert_eq!(unsafe { CAUGHT }, SIGUSR1 as usize);
+    println!("signal mask test passed!");
+
+    let pid = fork();
+    if pid == 0 {
+        loop {
+            yield_();
+        }
+    }
+    assert_eq!(kill(pid as usize, SIGKILL), 0);
+    let mut exit_code = 0;
+    assert_eq!(wait(&mut exit_code), pid);
+    assert_eq!(exit_code, -SIGKILL);
+    println!("kill test passed!");
+    exit(0);
+}